@@ -0,0 +1,65 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{columns, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct Columns;
+
+impl PluginCommand for Columns {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql columns"
+    }
+
+    fn usage(&self) -> &str {
+        "List the columns of a MSSQL table or view with their types, defaults and collations"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "table",
+                SyntaxShape::String,
+                "The table or view to describe",
+            )
+            .optional(
+                "pattern",
+                SyntaxShape::String,
+                "Glob matched against the column names",
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["schema", "types", "fields"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let table: String = call.req(0)?;
+        let pattern: Option<String> = call.opt(1)?;
+
+        let rows = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            columns(&mut client, &table, pattern.as_deref()).await
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,86 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{create_table_sql, run_batch, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct CreateTable;
+
+impl PluginCommand for CreateTable {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql create-table"
+    }
+
+    fn usage(&self) -> &str {
+        "Create a MSSQL table with a schema inferred from the pipeline table"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "The input is passed through after the table is created, so it can be piped \
+        straight into `mssql insert`."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "table",
+                SyntaxShape::String,
+                "The name of the table to create",
+            )
+            .named(
+                "primary-key",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "The columns that make up the primary key",
+                Some('k'),
+            )
+            .switch(
+                "dry-run",
+                "Return the CREATE TABLE statement without running it",
+                None,
+            )
+            .connection_flags()
+            .input_output_types(vec![
+                (Type::table(), Type::table()),
+                (Type::table(), Type::String),
+            ])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["ddl", "schema", "infer"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let table: String = call.req(0)?;
+        let primary_key: Vec<String> = call.get_flag("primary-key")?.unwrap_or_default();
+        let metadata = input.metadata();
+        let rows: Vec<Value> = input.into_iter().collect();
+
+        let sql = create_table_sql(&table, &rows, &primary_key)?;
+        if call.has_flag("dry-run")? {
+            return Ok(Value::string(sql, call.head).into_pipeline_data());
+        }
+
+        let args = ConnectionArgs::from_call(call)?;
+        task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            run_batch(&mut client, &sql).await
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data_with_metadata(metadata))
+    }
+}
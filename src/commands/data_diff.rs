@@ -0,0 +1,142 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoInterruptiblePipelineData, LabeledError, PipelineData, Signals, Signature,
+    SyntaxShape, Type,
+};
+
+use crate::{
+    data::{
+        ConnectionArgs, ConnectionFlags, DataDiffStream, DiffSide, DiffSource,
+        DEFAULT_DIFF_CHUNK_SIZE,
+    },
+    MssqlPlugin,
+};
+
+pub struct DataDiff;
+
+impl PluginCommand for DataDiff {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql data-diff"
+    }
+
+    fn usage(&self) -> &str {
+        "Compare the rows of two MSSQL tables or queries, matched by key"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Sources starting with SELECT or WITH are run as queries, anything else is read as a \
+        table name, e.g. dbo.Users or [Order Details]. The key must identify each row on both \
+        sides, a repeated key stops the comparison with an error. \
+        --source-connection and --target-connection take records of connection arguments \
+        applied over the connection flags. Rows only in the source are reported as added and \
+        rows only in the target as removed, with the old (target) and new (source) value of \
+        each differing column."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "source",
+                SyntaxShape::String,
+                "The table or query to compare from",
+            )
+            .required(
+                "target",
+                SyntaxShape::String,
+                "The table or query to compare to",
+            )
+            .required_named(
+                "key",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "The columns identifying a row on both sides",
+                Some('k'),
+            )
+            .named(
+                "source-connection",
+                SyntaxShape::Record(vec![]),
+                "Connection arguments for the source",
+                None,
+            )
+            .named(
+                "target-connection",
+                SyntaxShape::Record(vec![]),
+                "Connection arguments for the target",
+                None,
+            )
+            .named(
+                "chunk-size",
+                SyntaxShape::Int,
+                format!(
+                    "The number of rows hashed per chunk, default: {}",
+                    DEFAULT_DIFF_CHUNK_SIZE
+                ),
+                Some('c'),
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["compare", "rows", "etl", "verify"]
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let source: String = call.req(0)?;
+        let target: String = call.req(1)?;
+        let keys: Vec<String> = call.get_flag("key")?.unwrap_or_default();
+        let chunk_size = match call.get_flag::<i64>("chunk-size")? {
+            Some(size) if size < 1 => {
+                return Err(
+                    LabeledError::new("Chunk size must be at least 1").with_label(
+                        "too small",
+                        call.get_flag_span("chunk-size").unwrap_or(call.head),
+                    ),
+                )
+            }
+            Some(size) => size as usize,
+            None => DEFAULT_DIFF_CHUNK_SIZE,
+        };
+
+        let side_args = |flag: &str| match call.get_flag_value(flag) {
+            Some(overrides) => args.with_overrides(&overrides),
+            None => Ok(args.clone()),
+        };
+        let source_args = side_args("source-connection")?;
+        let target_args = side_args("target-connection")?;
+
+        let diff = task::block_on(async {
+            let source = DiffSide {
+                connection: plugin
+                    .connection_pool
+                    .get_or_create(engine, source_args)
+                    .await?,
+                source: DiffSource::parse(&source)?,
+            };
+            let target = DiffSide {
+                connection: plugin
+                    .connection_pool
+                    .get_or_create(engine, target_args)
+                    .await?,
+                source: DiffSource::parse(&target)?,
+            };
+            DataDiffStream::new(source, target, keys, chunk_size).await
+        })?;
+
+        let head = call.head;
+        Ok(diff
+            .map(move |value| value.with_span(head))
+            .into_pipeline_data(head, Signals::empty()))
+    }
+}
@@ -0,0 +1,51 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{Category, IntoPipelineData, LabeledError, PipelineData, Signature, Type, Value};
+
+use crate::{
+    data::{databases, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct Databases;
+
+impl PluginCommand for Databases {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql databases"
+    }
+
+    fn usage(&self) -> &str {
+        "List the databases on a MSSQL server with their state, size and last backups"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["list", "size", "backup"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+
+        let rows = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            databases(&mut client).await
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
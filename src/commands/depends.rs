@@ -0,0 +1,94 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{dependencies, ConnectionArgs, ConnectionFlags, DEFAULT_DEPENDS_DEPTH},
+    MssqlPlugin,
+};
+
+pub struct Depends;
+
+impl PluginCommand for Depends {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql depends"
+    }
+
+    fn usage(&self) -> &str {
+        "List the objects a MSSQL object depends on and the objects that depend on it"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Upstream rows are objects referenced by the object, downstream rows are objects \
+        referencing it. Both directions are followed recursively, with the object each row \
+        was reached from in the via column. When SQL Server cannot resolve the columns an \
+        object uses, e.g. because it no longer compiles, columns_error says why."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "object",
+                SyntaxShape::String,
+                "The table, view, procedure or function, as schema.name",
+            )
+            .named(
+                "column",
+                SyntaxShape::String,
+                "Only follow references to this column of the object",
+                Some('c'),
+            )
+            .named(
+                "max-depth",
+                SyntaxShape::Int,
+                format!(
+                    "How many levels of dependencies to follow, default: {}",
+                    DEFAULT_DEPENDS_DEPTH
+                ),
+                Some('m'),
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dependencies", "references", "usage", "impact"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let object: String = call.req(0)?;
+        let column: Option<String> = call.get_flag("column")?;
+        let max_depth = match call.get_flag::<i64>("max-depth")? {
+            Some(depth) if depth < 1 => {
+                return Err(
+                    LabeledError::new("Max depth must be at least 1").with_label(
+                        "too small",
+                        call.get_flag_span("max-depth").unwrap_or(call.head),
+                    ),
+                )
+            }
+            Some(depth) => depth as usize,
+            None => DEFAULT_DEPENDS_DEPTH,
+        };
+
+        let rows = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            dependencies(&mut client, &object, column.as_deref(), max_depth).await
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
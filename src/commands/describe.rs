@@ -0,0 +1,59 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+};
+
+use crate::{
+    data::{describe, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct Describe;
+
+impl PluginCommand for Describe {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql describe"
+    }
+
+    fn usage(&self) -> &str {
+        "Describe a MSSQL table with its columns, keys, constraints, indexes and triggers"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "table",
+                SyntaxShape::String,
+                "The table to describe, as schema.table",
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::record())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["schema", "keys", "indexes", "constraints", "definition"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let table: String = call.req(0)?;
+
+        let description = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            describe(&mut client, &table).await
+        })?;
+
+        Ok(description.with_span(call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,81 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{ConnectionArgs, ConnectionFlags, ErFormat, ErModel},
+    MssqlPlugin,
+};
+
+pub struct ErDiagram;
+
+impl PluginCommand for ErDiagram {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql er-diagram"
+    }
+
+    fn usage(&self) -> &str {
+        "Draw the foreign key relationships of a MSSQL database as Mermaid or Graphviz text"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .named(
+                "schema",
+                SyntaxShape::String,
+                "Only draw the tables of this schema",
+                Some('S'),
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "The output format, mermaid or dot, default: mermaid",
+                Some('f'),
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::String)
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "erd",
+            "relationships",
+            "foreign keys",
+            "mermaid",
+            "graphviz",
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let schema: Option<String> = call.get_flag("schema")?;
+        let format = match call.get_flag::<String>("format")? {
+            Some(format) => ErFormat::parse(&format).ok_or_else(|| {
+                LabeledError::new(format!("Unknown format {format}")).with_label(
+                    "expected mermaid or dot",
+                    call.get_flag_span("format").unwrap_or(call.head),
+                )
+            })?,
+            None => ErFormat::Mermaid,
+        };
+
+        let diagram = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            ErModel::read(&mut client, schema.as_deref()).await
+        })?;
+
+        Ok(Value::string(diagram.render(format), call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,138 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+    Value,
+};
+
+use crate::{
+    data::{execute_each, ConnectionArgs, ConnectionFlags, RowStatus, DEFAULT_EXEC_BATCH_SIZE},
+    MssqlPlugin,
+};
+
+pub struct Exec;
+
+impl PluginCommand for Exec {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql exec"
+    }
+
+    fn usage(&self) -> &str {
+        "Execute a statement against a MSSQL database"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "With --each the statement runs once per input row, with the row's fields bound to \
+        @name parameters. All rows run in one transaction that is rolled back if any row fails."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("statement", SyntaxShape::String, "The statement to execute")
+            .switch(
+                "each",
+                "Run the statement once for every row of the input table",
+                Some('e'),
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Int,
+                format!(
+                    "The number of rows sent per round trip with --each, default: {}",
+                    DEFAULT_EXEC_BATCH_SIZE
+                ),
+                Some('b'),
+            )
+            .connection_flags()
+            .input_output_types(vec![
+                (Type::Nothing, Type::record()),
+                (Type::table(), Type::table()),
+            ])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["execute", "update", "parameters"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let statement: String = call.req(0)?;
+        let each = call.has_flag("each")?;
+        let batch_size = match call.get_flag::<i64>("batch-size")? {
+            Some(size) if size > 0 => size as usize,
+            Some(_) => {
+                return Err(LabeledError::new("Invalid batch size").with_label(
+                    "must be greater than zero",
+                    call.get_flag_span("batch-size").unwrap_or(call.head),
+                ))
+            }
+            None => DEFAULT_EXEC_BATCH_SIZE,
+        };
+        let span = call.head;
+
+        if !each {
+            let total = task::block_on(async {
+                let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+                let mut client = connection.client().await;
+                client
+                    .execute(statement, &[])
+                    .await
+                    .map(|result| result.total())
+                    .map_err(|e| {
+                        LabeledError::new(format!("Error executing statement: {e}"))
+                            .with_label("failed", call.head)
+                    })
+            })?;
+
+            let value = Value::record(
+                record! { "rows_affected" => Value::int(total as i64, span) },
+                span,
+            );
+            return Ok(value.into_pipeline_data());
+        }
+
+        let results = task::block_on(async {
+            let connection = plugin
+                .connection_pool
+                .get_for_input(engine, args, &input)
+                .await?;
+            let mut client = connection.client().await;
+            execute_each(&mut client, &statement, input.into_iter(), batch_size).await
+        })?;
+
+        let rows = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let (status, error) = match result.status {
+                    RowStatus::Ok => ("ok", Value::nothing(span)),
+                    RowStatus::RolledBack => ("rolled back", Value::nothing(span)),
+                    RowStatus::Error(e) => ("error", Value::string(e, span)),
+                };
+                Value::record(
+                    record! {
+                        "row" => Value::int(index as i64, span),
+                        "status" => Value::string(status, span),
+                        "rows_affected" => result
+                            .rows_affected
+                            .map(|count| Value::int(count as i64, span))
+                            .unwrap_or(Value::nothing(span)),
+                        "error" => error,
+                    },
+                    span,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, span).into_pipeline_data())
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, ShellError, Signature,
+    SyntaxShape, Type, Value,
+};
+
+use super::query::get_query;
+use crate::{
+    data::{
+        export, Compression, ConnectionArgs, ConnectionFlags, ExportFormat, ExportOptions,
+        ParseFlags, ParseOptions, QuerySource, TableIterator,
+    },
+    MssqlPlugin,
+};
+
+pub struct Export;
+
+impl PluginCommand for Export {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql export"
+    }
+
+    fn usage(&self) -> &str {
+        "Write the results of a query straight to csv, tsv, jsonl or parquet files"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Rows are written as they arrive, holding only a bounded buffer of rows, or one parquet \
+        row group, in memory. With --max-rows the output is split into numbered files, e.g. \
+        extract_1.csv.gz, extract_2.csv.gz. Parquet column types are taken from the first row \
+        group, and --compression applies to its pages."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .named(
+                "query",
+                SyntaxShape::String,
+                "The query to run against the database",
+                Some('q'),
+            )
+            .named(
+                "file",
+                SyntaxShape::Filepath,
+                "The path to a file containing the query",
+                Some('f'),
+            )
+            .required_named(
+                "format",
+                SyntaxShape::String,
+                "The file format: csv, tsv, jsonl or parquet",
+                None,
+            )
+            .required_named(
+                "output",
+                SyntaxShape::Filepath,
+                "The file to write",
+                Some('o'),
+            )
+            .named(
+                "compression",
+                SyntaxShape::String,
+                "Compress the output with gzip or zstd, default: none",
+                None,
+            )
+            .named(
+                "max-rows",
+                SyntaxShape::Int,
+                "Start a new file after this many rows",
+                None,
+            )
+            .connection_flags()
+            .parse_flags()
+            .input_output_types(vec![(Type::Nothing, Type::record())])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["extract", "save", "csv", "parquet", "jsonl"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let query = get_query(&QuerySource::from_call(call)?)?;
+        let parse_options = ParseOptions::from_call(call)?;
+        let output: String = call.get_flag("output")?.unwrap_or_default();
+        let max_rows = match call.get_flag::<i64>("max-rows")? {
+            Some(max_rows) if max_rows < 1 => {
+                return Err(LabeledError::new("--max-rows must be at least 1")
+                    .with_label("not a positive row count", call.head))
+            }
+            max_rows => max_rows.map(|max_rows| max_rows as usize),
+        };
+        let options = ExportOptions {
+            format: ExportFormat::parse(&call.get_flag::<String>("format")?.unwrap_or_default())?,
+            output: PathBuf::from(engine.get_current_dir()?).join(output),
+            compression: match call.get_flag::<String>("compression")? {
+                Some(compression) => Compression::parse(&compression)?,
+                None => Compression::default(),
+            },
+            max_rows,
+        };
+
+        let (sender, receiver) = async_std::channel::bounded(args.as_ref().buffer_size);
+        let (done_sender, done) = async_std::channel::bounded(1);
+        let connection = task::block_on(plugin.connection_pool.get_or_create(engine, args))?;
+        task::spawn(async move {
+            connection.run_query(query, parse_options, sender).await;
+            let _ = done_sender.send(()).await;
+        });
+
+        // A query task that ends without finishing must not pass for a complete extract.
+        let span = call.head;
+        let unfinished = std::iter::from_fn(move || match done.recv_blocking() {
+            Ok(()) => None,
+            Err(_) => Some(Value::error(
+                ShellError::LabeledError(Box::new(
+                    LabeledError::new("The query stopped before all rows were read")
+                        .with_label("incomplete export", span),
+                )),
+                span,
+            )),
+        });
+        let files = export(TableIterator::new(receiver).chain(unfinished), &options)?;
+
+        let rows = files.iter().map(|file| file.rows as i64).sum();
+        let bytes = files.iter().map(|file| file.bytes as i64).sum();
+        Ok(Value::record(
+            record! {
+                "files" => Value::list(
+                    files.into_iter().map(|file| file.into_value(call.head)).collect(),
+                    call.head,
+                ),
+                "rows" => Value::int(rows, call.head),
+                "bytes" => Value::filesize(bytes, call.head),
+            },
+            call.head,
+        )
+        .into_pipeline_data())
+    }
+}
@@ -0,0 +1,112 @@
+use async_std::task;
+use fancy_regex::Regex;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{grep_data, grep_definitions, ConnectionArgs, ConnectionFlags, DEFAULT_GREP_LIMIT},
+    MssqlPlugin,
+};
+
+pub struct Grep;
+
+impl PluginCommand for Grep {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql grep"
+    }
+
+    fn usage(&self) -> &str {
+        "Search the definitions of MSSQL views, procedures, functions and triggers"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "The pattern is a regular expression matched against each line of the definitions. \
+        With --data, string columns of the selected tables are searched instead, and the \
+        pattern is matched as plain text by a parameterized LIKE query."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("pattern", SyntaxShape::String, "The pattern to search for")
+            .switch(
+                "ignore-case",
+                "Match the regex case insensitively",
+                Some('I'),
+            )
+            .switch("data", "Search the data of string columns instead", None)
+            .named(
+                "tables",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Glob patterns of the tables searched with --data, default: all tables",
+                Some('T'),
+            )
+            .named(
+                "limit",
+                SyntaxShape::Int,
+                format!(
+                    "The max number of rows returned per table with --data, default: {}",
+                    DEFAULT_GREP_LIMIT
+                ),
+                Some('l'),
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["search", "find", "definition", "regex", "like"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let pattern: String = call.req(0)?;
+        let data = call.has_flag("data")?;
+        let tables: Vec<String> = call.get_flag("tables")?.unwrap_or_default();
+        let limit = match call.get_flag::<i64>("limit")? {
+            Some(limit) if limit < 1 => {
+                return Err(LabeledError::new("Limit must be at least 1").with_label(
+                    "too small",
+                    call.get_flag_span("limit").unwrap_or(call.head),
+                ))
+            }
+            Some(limit) => limit as usize,
+            None => DEFAULT_GREP_LIMIT,
+        };
+
+        let regex = match data {
+            true => None,
+            false => {
+                let source = match call.has_flag("ignore-case")? {
+                    true => format!("(?i){pattern}"),
+                    false => pattern.clone(),
+                };
+                Some(Regex::new(&source).map_err(|e| {
+                    LabeledError::new(format!("Invalid regex: {e}"))
+                        .with_label("invalid regex", call.positional[0].span())
+                })?)
+            }
+        };
+
+        let rows = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            match &regex {
+                Some(regex) => grep_definitions(&mut client, regex).await,
+                None => grep_data(&mut client, &pattern, &tables, limit).await,
+            }
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+    Value,
+};
+
+use super::insert::bulk_options_from_call;
+use crate::{
+    data::{
+        bulk_insert_rows, insert_columns, ConnectionArgs, ConnectionFlags, ImportFormat,
+        ImportRows, DEFAULT_BATCH_SIZE,
+    },
+    MssqlPlugin,
+};
+
+pub struct Import;
+
+impl PluginCommand for Import {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql import"
+    }
+
+    fn usage(&self) -> &str {
+        "Bulk load a CSV, TSV or JSON lines file into a MSSQL table"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "The file is read as it is loaded, without becoming a Nushell table. Headers and fields \
+        are matched to columns by name and converted to each column's type. Rows that cannot \
+        be read or converted are written to the reject file as JSON lines of {line, error, row} \
+        and the rest are loaded."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("file", SyntaxShape::Filepath, "The file to load")
+            .required_named("table", SyntaxShape::String, "The table to load into", None)
+            .named(
+                "format",
+                SyntaxShape::String,
+                "The file format: csv, tsv or jsonl, default: from the file extension",
+                None,
+            )
+            .named(
+                "reject-file",
+                SyntaxShape::Filepath,
+                "Where to write rejected rows, default: the file name with .rejects.jsonl",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Int,
+                format!(
+                    "The number of rows sent per bulk load batch, default: {}",
+                    DEFAULT_BATCH_SIZE
+                ),
+                Some('b'),
+            )
+            .switch(
+                "keep-identity",
+                "Insert identity values from the file instead of generating them",
+                Some('k'),
+            )
+            .switch(
+                "tablock",
+                "Hold an exclusive table lock until the load completes",
+                None,
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::record())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["bulk", "load", "bcp", "csv", "jsonl"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let current_dir = PathBuf::from(engine.get_current_dir()?);
+        let path = current_dir.join(call.req::<String>(0)?);
+        let table: String = call.get_flag("table")?.unwrap_or_default();
+        let options = bulk_options_from_call(call)?;
+
+        let format = match call.get_flag::<String>("format")? {
+            Some(format) => ImportFormat::parse(&format)?,
+            None => ImportFormat::from_path(&path).ok_or_else(|| {
+                LabeledError::new("Cannot tell the format of the file from its extension")
+                    .with_label("use --format csv, tsv or jsonl", call.head)
+            })?,
+        };
+        let reject_path = match call.get_flag::<String>("reject-file")? {
+            Some(reject_file) => current_dir.join(reject_file),
+            None => {
+                let mut name = path.file_name().unwrap_or_default().to_os_string();
+                name.push(".rejects.jsonl");
+                path.with_file_name(name)
+            }
+        };
+
+        let (summary, rejected, reject_path) = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+
+            let columns = insert_columns(&mut client, &table, &options).await?;
+            let mut rows = ImportRows::open(&path, format, &columns, reject_path)?;
+            let summary =
+                bulk_insert_rows(&mut client, &table, &columns, &mut rows, &options).await;
+            rows.finish()?;
+
+            let reject_path = rows.reject_path().map(|path| path.to_path_buf());
+            Ok::<_, LabeledError>((summary?, rows.rejected, reject_path))
+        })?;
+
+        let span = call.head;
+        let value = Value::record(
+            record! {
+                "table" => Value::string(table, span),
+                "rows_inserted" => Value::int(summary.rows as i64, span),
+                "rows_rejected" => Value::int(rejected as i64, span),
+                "batches" => Value::int(summary.batches as i64, span),
+                "reject_file" => match reject_path {
+                    Some(path) => Value::string(path.to_string_lossy(), span),
+                    None => Value::nothing(span),
+                },
+            },
+            span,
+        );
+
+        Ok(value.into_pipeline_data())
+    }
+}
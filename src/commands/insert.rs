@@ -0,0 +1,115 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+    Value,
+};
+
+use crate::{
+    data::{bulk_insert, BulkOptions, ConnectionArgs, ConnectionFlags, DEFAULT_BATCH_SIZE},
+    MssqlPlugin,
+};
+
+pub struct Insert;
+
+impl PluginCommand for Insert {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql insert"
+    }
+
+    fn usage(&self) -> &str {
+        "Bulk insert a table from the pipeline into a MSSQL table"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Record fields are matched to columns by name and converted to each column's type."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("table", SyntaxShape::String, "The table to insert into")
+            .named(
+                "batch-size",
+                SyntaxShape::Int,
+                format!(
+                    "The number of rows sent per bulk load batch, default: {}",
+                    DEFAULT_BATCH_SIZE
+                ),
+                Some('b'),
+            )
+            .switch(
+                "keep-identity",
+                "Insert identity values from the input instead of generating them",
+                Some('k'),
+            )
+            .switch(
+                "tablock",
+                "Hold an exclusive table lock until the load completes",
+                None,
+            )
+            .connection_flags()
+            .input_output_type(Type::table(), Type::record())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["bulk", "load", "bcp"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let table: String = call.req(0)?;
+        let options = bulk_options_from_call(call)?;
+
+        let summary = task::block_on(async {
+            let connection = plugin
+                .connection_pool
+                .get_for_input(engine, args, &input)
+                .await?;
+            let mut client = connection.client().await;
+            bulk_insert(&mut client, &table, input.into_iter(), &options).await
+        })?;
+
+        let span = call.head;
+        let value = Value::record(
+            record! {
+                "table" => Value::string(table, span),
+                "rows_inserted" => Value::int(summary.rows as i64, span),
+                "batches" => Value::int(summary.batches as i64, span),
+            },
+            span,
+        );
+
+        Ok(value.into_pipeline_data())
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub(crate) fn bulk_options_from_call(
+    call: &nu_plugin::EvaluatedCall,
+) -> Result<BulkOptions, LabeledError> {
+    let batch_size = match call.get_flag::<i64>("batch-size")? {
+        Some(size) if size > 0 => size as usize,
+        Some(_) => {
+            return Err(LabeledError::new("Invalid batch size").with_label(
+                "must be greater than zero",
+                call.get_flag_span("batch-size").unwrap_or(call.head),
+            ))
+        }
+        None => DEFAULT_BATCH_SIZE,
+    };
+
+    Ok(BulkOptions {
+        batch_size,
+        keep_identity: call.has_flag("keep-identity")?,
+        tablock: call.has_flag("tablock")?,
+    })
+}
@@ -1,5 +1,41 @@
+mod columns;
+mod create_table;
+mod data_diff;
+mod databases;
+mod depends;
+mod describe;
+mod er_diagram;
+mod exec;
+mod export;
+mod grep;
+mod import;
+mod insert;
 mod mssql;
+mod proc;
 mod query;
+mod schema_diff;
+mod script;
+mod tables;
+mod to_inserts;
+mod upsert;
 
+pub use columns::Columns;
+pub use create_table::CreateTable;
+pub use data_diff::DataDiff;
+pub use databases::Databases;
+pub use depends::Depends;
+pub use describe::Describe;
+pub use er_diagram::ErDiagram;
+pub use exec::Exec;
+pub use export::Export;
+pub use grep::Grep;
+pub use import::Import;
+pub use insert::Insert;
 pub use mssql::Mssql;
+pub use proc::Proc;
 pub use query::Query;
+pub use schema_diff::SchemaDiff;
+pub use script::Script;
+pub use tables::Tables;
+pub use to_inserts::ToInserts;
+pub use upsert::Upsert;
@@ -0,0 +1,70 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Record, Signature, SyntaxShape, Type,
+};
+
+use crate::{
+    data::{call_procedure, ConnectionArgs, ConnectionFlags, ParseFlags, ParseOptions},
+    MssqlPlugin,
+};
+
+pub struct Proc;
+
+impl PluginCommand for Proc {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql proc"
+    }
+
+    fn usage(&self) -> &str {
+        "Call a stored procedure, returning its result sets, output parameters and return value"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Parameters are matched by name, with or without the leading @, and converted to the \
+        types declared by the procedure. Result sets are read into records as mssql query \
+        reads them, following the same parse flags and naming of unnamed and repeated columns."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("name", SyntaxShape::String, "The procedure to call")
+            .named(
+                "params",
+                SyntaxShape::Record(vec![]),
+                "The parameter values to pass, by name",
+                None,
+            )
+            .connection_flags()
+            .parse_flags()
+            .input_output_type(Type::Nothing, Type::record())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["stored procedure", "execute", "output"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let procedure: String = call.req(0)?;
+        let inputs: Record = call.get_flag("params")?.unwrap_or_default();
+        let options = ParseOptions::from_call(call)?;
+
+        let result = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            call_procedure(&mut client, &procedure, &inputs, options).await
+        })?;
+
+        Ok(result.into_value(call.head).into_pipeline_data())
+    }
+}
@@ -1,4 +1,6 @@
-use crate::data::{ConnectionArgs, QuerySource};
+use crate::data::{
+    query_with_schema, ConnectionArgs, ConnectionFlags, ParseFlags, ParseOptions, QuerySource,
+};
 use async_std::task;
 use nu_plugin::PluginCommand;
 use nu_protocol::{
@@ -21,6 +23,15 @@ impl<'a> PluginCommand for Query {
         "Run a query against a MSSQL database"
     }
 
+    fn extra_usage(&self) -> &str {
+        "Record fields follow the SELECT order. Unnamed columns are named column1, column2, ... \
+        by position, and repeated names follow --duplicate-columns, which stops the query with \
+        an error under the error policy. When the first result set has no rows, a single \
+        {columns, rows} record like --with-schema returns stands in for it, so its columns and \
+        their types are still known. Its column entries, as those of --with-schema, each carry \
+        the duplicate column policy their name was chosen under."
+    }
+
     fn signature(&self) -> Signature {
         Signature::build(PluginCommand::name(self))
             .named(
@@ -35,35 +46,12 @@ impl<'a> PluginCommand for Query {
                 "The path to a file containing the query",
                 Some('f'),
             )
-            .named(
-                "server",
-                SyntaxShape::String,
-                "The server to connect to, default: localhost",
-                Some('s'),
-            )
-            .named(
-                "instance",
-                SyntaxShape::String,
-                "The server instance to connect to",
-                Some('i'),
-            )
-            .named(
-                "database",
-                SyntaxShape::String,
-                "The database to connect to, default: master",
-                Some('d'),
-            )
-            .named(
-                "user",
-                SyntaxShape::String,
-                "The user to connect as, default: sa",
-                Some('u'),
-            )
-            .named(
-                "password",
-                SyntaxShape::String,
-                "The password to connect with",
-                Some('p'),
+            .connection_flags()
+            .parse_flags()
+            .switch(
+                "with-schema",
+                "Return the first result set as {columns, rows}, with the type of each column",
+                None,
             )
             .named(
                 "row-buffer",
@@ -74,8 +62,10 @@ impl<'a> PluginCommand for Query {
                 ),
                 Some('b'),
             )
-            .input_output_type(Type::Custom("MssqlClient".into()), Type::table())
-            .switch("trust-cert", "Trust the server certificate", Some('t'))
+            .input_output_types(vec![
+                (Type::Custom("MssqlClient".into()), Type::table()),
+                (Type::Custom("MssqlClient".into()), Type::record()),
+            ])
             .category(nu_protocol::Category::Database)
     }
 
@@ -89,29 +79,25 @@ impl<'a> PluginCommand for Query {
         let args = ConnectionArgs::from_call(call)?;
         let query = QuerySource::from_call(call)?;
         let query = get_query(&query)?;
+        let options = ParseOptions::from_call(call)?;
+
+        if call.has_flag("with-schema")? {
+            let result = task::block_on(async {
+                let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+                let mut client = connection.client().await;
+                query_with_schema(&mut client, &query.item, options).await
+            })?;
+            return Ok(result.with_span(call.head).into_pipeline_data());
+        }
+
         let (sender, receiver) = async_std::channel::bounded(args.as_ref().buffer_size);
 
-        let connection = task::block_on(async {
-            match plugin.connection_pool.get(&args, true) {
-                Ok(Some(connection)) => {
-                    eprintln!("Connection pool returned existing connection");
-                    Ok(connection)
-                }
-                Ok(None) => match plugin.connection_pool.create_connection(engine, args).await {
-                    Ok(connection) => {
-                        eprintln!("Connection pool created new connection");
-                        Ok(connection)
-                    }
-                    Err(e) => Err(e),
-                },
-                Err(e) => Err(e),
-            }
-        });
+        let connection = task::block_on(plugin.connection_pool.get_or_create(engine, args));
 
         match connection {
             Ok(connection) => {
                 task::spawn(async move {
-                    _ = &connection.run_query(query, sender).await;
+                    _ = &connection.run_query(query, options, sender).await;
                 });
             }
             Err(e) => {
@@ -125,7 +111,7 @@ impl<'a> PluginCommand for Query {
     }
 }
 
-fn get_query(query: &QuerySource) -> Result<Spanned<String>, LabeledError> {
+pub(crate) fn get_query(query: &QuerySource) -> Result<Spanned<String>, LabeledError> {
     match query {
         QuerySource::Query(query, span) => Ok(query.clone().into_spanned(span.clone())),
         QuerySource::File(file, span) => match std::fs::read_to_string(file) {
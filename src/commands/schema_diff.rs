@@ -0,0 +1,106 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{migration_script, schema_diff, schema_snapshot, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct SchemaDiff;
+
+impl PluginCommand for SchemaDiff {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql schema-diff"
+    }
+
+    fn usage(&self) -> &str {
+        "Compare the schemas of two MSSQL databases"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "--source and --target take records of connection arguments (server, instance, \
+        database, user, password, trust_cert) applied over the connection flags. Objects only \
+        in the source are reported as added and objects only in the target as removed, so \
+        --script produces a migration bringing the target in line with the source. Identity \
+        changes need the table to be rebuilt and are only flagged as comments in the script."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required_named(
+                "source",
+                SyntaxShape::Record(vec![]),
+                "Connection arguments of the database with the desired schema",
+                None,
+            )
+            .required_named(
+                "target",
+                SyntaxShape::Record(vec![]),
+                "Connection arguments of the database to compare against",
+                None,
+            )
+            .switch(
+                "script",
+                "Return a migration script instead of the differences",
+                None,
+            )
+            .connection_flags()
+            .input_output_types(vec![
+                (Type::Nothing, Type::table()),
+                (Type::Nothing, Type::String),
+            ])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["compare", "migration", "ddl", "drift"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let source_args =
+            args.with_overrides(&call.get_flag_value("source").unwrap_or_default())?;
+        let target_args =
+            args.with_overrides(&call.get_flag_value("target").unwrap_or_default())?;
+        let script = call.has_flag("script")?;
+
+        task::block_on(async {
+            // Each client is released before the next is locked, as both sides may
+            // share a pooled connection.
+            let source = plugin
+                .connection_pool
+                .get_or_create(engine, source_args)
+                .await?;
+            let source_schema = schema_snapshot(&mut *source.client().await).await?;
+
+            let target = plugin
+                .connection_pool
+                .get_or_create(engine, target_args)
+                .await?;
+            let target_schema = schema_snapshot(&mut *target.client().await).await?;
+
+            let differences = schema_diff(&source_schema, &target_schema);
+            if script {
+                let script = migration_script(&mut *source.client().await, &differences).await?;
+                return Ok(Value::string(script, call.head).into_pipeline_data());
+            }
+
+            let rows = differences
+                .into_iter()
+                .map(|difference| difference.into_value(call.head))
+                .collect();
+            Ok(Value::list(rows, call.head).into_pipeline_data())
+        })
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+    Value,
+};
+
+use crate::{
+    data::{schema_objects, script_object, ConnectionArgs, ConnectionFlags, ObjectScript},
+    MssqlPlugin,
+};
+
+pub struct Script;
+
+impl PluginCommand for Script {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql script"
+    }
+
+    fn usage(&self) -> &str {
+        "Script the CREATE statement of a MSSQL table, view, procedure or function"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "Tables are reconstructed from the catalog views, including their constraints, indexes \
+        and triggers. Other objects are scripted from their stored definition. With --schema, \
+        every table, view, function and procedure of the schema is scripted, views, functions \
+        and procedures after the objects they reference. Foreign keys are added with ALTER \
+        TABLE after every object, as scripts of type `foreign keys`, so that tables referencing \
+        each other can be created in any order. With --drop, they are dropped before any \
+        object, in scripts of type `drop foreign keys`."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .optional(
+                "object",
+                SyntaxShape::String,
+                "The object to script, as schema.name",
+            )
+            .named(
+                "schema",
+                SyntaxShape::String,
+                "Script every object in this schema",
+                Some('S'),
+            )
+            .switch("drop", "Precede each script with DROP ... IF EXISTS", None)
+            .named(
+                "output-dir",
+                SyntaxShape::Directory,
+                "Write one schema.name.sql file per object into this directory",
+                Some('o'),
+            )
+            .connection_flags()
+            .input_output_types(vec![
+                (Type::Nothing, Type::String),
+                (Type::Nothing, Type::table()),
+            ])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["ddl", "create", "definition", "generate"]
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let object: Option<String> = call.opt(0)?;
+        let schema: Option<String> = call.get_flag("schema")?;
+        let drop = call.has_flag("drop")?;
+        let output_dir: Option<String> = call.get_flag("output-dir")?;
+
+        if object.is_some() == schema.is_some() {
+            return Err(LabeledError::new("Expected either an object or --schema")
+                .with_label("script one object or one schema", call.head));
+        }
+
+        let scripts = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+
+            let objects = match (&object, &schema) {
+                (Some(object), _) => vec![object.clone()],
+                (None, Some(schema)) => schema_objects(&mut client, schema).await?,
+                (None, None) => vec![],
+            };
+
+            let mut scripts = vec![];
+            for object in objects {
+                scripts.push(script_object(&mut client, &object, drop).await?);
+            }
+            Ok::<_, LabeledError>(with_foreign_keys(scripts))
+        })?;
+
+        let Some(output_dir) = output_dir else {
+            if object.is_some() {
+                let script = scripts
+                    .into_iter()
+                    .map(|script| script.script)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                return Ok(Value::string(script, call.head).into_pipeline_data());
+            }
+
+            let rows = scripts
+                .into_iter()
+                .map(|script| {
+                    Value::record(
+                        record! {
+                            "name" => Value::string(script.name, call.head),
+                            "type" => Value::string(script.kind, call.head),
+                            "script" => Value::string(script.script, call.head),
+                        },
+                        call.head,
+                    )
+                })
+                .collect();
+            return Ok(Value::list(rows, call.head).into_pipeline_data());
+        };
+
+        let directory = PathBuf::from(engine.get_current_dir()?).join(output_dir);
+        std::fs::create_dir_all(&directory).map_err(|e| {
+            LabeledError::new(format!("Error creating {}: {e}", directory.display()))
+        })?;
+
+        let rows = scripts
+            .into_iter()
+            .map(
+                |ObjectScript {
+                     name, kind, script, ..
+                 }| {
+                    let path = match kind {
+                        "foreign keys" => directory.join(format!("{name}.foreign_keys.sql")),
+                        "drop foreign keys" => {
+                            directory.join(format!("{name}.drop_foreign_keys.sql"))
+                        }
+                        _ => directory.join(format!("{name}.sql")),
+                    };
+                    std::fs::write(&path, script).map_err(|e| {
+                        LabeledError::new(format!("Error writing {}: {e}", path.display()))
+                    })?;
+                    Ok(Value::record(
+                        record! {
+                            "name" => Value::string(name, call.head),
+                            "type" => Value::string(kind, call.head),
+                            "path" => Value::string(path.to_string_lossy(), call.head),
+                        },
+                        call.head,
+                    ))
+                },
+            )
+            .collect::<Result<Vec<_>, LabeledError>>()?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
+
+/// Moves the foreign keys of the tables into scripts of their own, after every
+/// object, so that the tables they reference are created first. With --drop,
+/// scripts dropping them come before every object, so that no table is
+/// dropped while a key still references it.
+fn with_foreign_keys(scripts: Vec<ObjectScript>) -> Vec<ObjectScript> {
+    let split = |kind: &'static str, text: fn(&ObjectScript) -> &String| {
+        scripts
+            .iter()
+            .filter(|script| !text(script).is_empty())
+            .map(|script| ObjectScript {
+                name: script.name.clone(),
+                kind,
+                script: text(script).clone(),
+                foreign_keys: String::new(),
+                drop_foreign_keys: String::new(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let drops = split("drop foreign keys", |script| &script.drop_foreign_keys);
+    let foreign_keys = split("foreign keys", |script| &script.foreign_keys);
+    drops
+        .into_iter()
+        .chain(scripts)
+        .chain(foreign_keys)
+        .collect()
+}
@@ -0,0 +1,59 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{tables, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct Tables;
+
+impl PluginCommand for Tables {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql tables"
+    }
+
+    fn usage(&self) -> &str {
+        "List the tables and views in a MSSQL database with their row counts and sizes"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .optional(
+                "pattern",
+                SyntaxShape::String,
+                "Glob matched against the name, or against schema.name when it contains a dot",
+            )
+            .connection_flags()
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["list", "views", "size", "rows"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let pattern: Option<String> = call.opt(0)?;
+
+        let rows = task::block_on(async {
+            let connection = plugin.connection_pool.get_or_create(engine, args).await?;
+            let mut client = connection.client().await;
+            tables(&mut client, pattern.as_deref()).await
+        })?;
+
+        Ok(Value::list(rows, call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,85 @@
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type, Value,
+};
+
+use crate::{
+    data::{insert_script, MAX_INSERT_BATCH_SIZE},
+    MssqlPlugin,
+};
+
+pub struct ToInserts;
+
+impl PluginCommand for ToInserts {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql to-inserts"
+    }
+
+    fn usage(&self) -> &str {
+        "Convert the pipeline table to a T-SQL script of INSERT statements"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "No connection is needed, so any table can be scripted, e.g. to seed a test database. \
+        Strings are written as N'' literals and dates in ISO 8601 form. Durations are written \
+        as nanoseconds, matching the BIGINT columns of `mssql create-table`. Unlike `mssql \
+        insert`, which converts them for TIME columns, the column types are not known here, so \
+        query TIME columns with --time string to script them as times."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required_named(
+                "table",
+                SyntaxShape::String,
+                "The table to insert into",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Int,
+                format!(
+                    "The number of rows per INSERT statement, at most and by default: {}",
+                    MAX_INSERT_BATCH_SIZE
+                ),
+                Some('b'),
+            )
+            .switch(
+                "identity-insert",
+                "Wrap the statements in SET IDENTITY_INSERT so identity values can be inserted",
+                None,
+            )
+            .input_output_types(vec![(Type::table(), Type::String)])
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["script", "seed", "sql", "values"]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let table: String = call.get_flag("table")?.unwrap_or_default();
+        let batch_size = match call.get_flag::<i64>("batch-size")? {
+            Some(size) if (1..=MAX_INSERT_BATCH_SIZE as i64).contains(&size) => size as usize,
+            Some(_) => {
+                return Err(LabeledError::new("Invalid batch size").with_label(
+                    format!("must be between 1 and {MAX_INSERT_BATCH_SIZE}"),
+                    call.get_flag_span("batch-size").unwrap_or(call.head),
+                ))
+            }
+            None => MAX_INSERT_BATCH_SIZE,
+        };
+        let rows: Vec<Value> = input.into_iter().collect();
+
+        let script = insert_script(&table, &rows, batch_size, call.has_flag("identity-insert")?)?;
+        Ok(Value::string(script, call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,106 @@
+use async_std::task;
+use nu_plugin::PluginCommand;
+use nu_protocol::{
+    record, Category, IntoPipelineData, LabeledError, PipelineData, Signature, SyntaxShape, Type,
+    Value,
+};
+
+use crate::{
+    data::{upsert, ConnectionArgs, ConnectionFlags},
+    MssqlPlugin,
+};
+
+pub struct Upsert;
+
+impl PluginCommand for Upsert {
+    type Plugin = MssqlPlugin;
+
+    fn name(&self) -> &str {
+        "mssql upsert"
+    }
+
+    fn usage(&self) -> &str {
+        "Insert or update rows from the pipeline into a MSSQL table using MERGE"
+    }
+
+    fn extra_usage(&self) -> &str {
+        "The input is staged in a temporary table with bulk load, then merged on the key columns. \
+        Only the columns present in the input are updated."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required("table", SyntaxShape::String, "The table to merge into")
+            .named(
+                "key",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "The columns used to match input rows to existing rows",
+                Some('k'),
+            )
+            .switch(
+                "delete-missing",
+                "Delete rows from the table that are not in the input",
+                None,
+            )
+            .connection_flags()
+            .input_output_type(Type::table(), Type::record())
+            .category(Category::Database)
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["merge", "sync"]
+    }
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let args = ConnectionArgs::from_call(call)?;
+        let table: String = call.req(0)?;
+        let keys: Vec<String> = match call.get_flag("key")? {
+            Some(keys) => keys,
+            None => {
+                return Err(LabeledError::new("No key columns specified")
+                    .with_label("use --key to choose the columns to match on", call.head))
+            }
+        };
+        if keys.is_empty() {
+            return Err(LabeledError::new("No key columns specified").with_label(
+                "at least one key column is required",
+                call.get_flag_span("key").unwrap_or(call.head),
+            ));
+        }
+        let delete_missing = call.has_flag("delete-missing")?;
+
+        let summary = task::block_on(async {
+            let connection = plugin
+                .connection_pool
+                .get_for_input(engine, args, &input)
+                .await?;
+            let mut client = connection.client().await;
+            upsert(
+                &mut client,
+                &table,
+                &keys,
+                input.into_iter(),
+                delete_missing,
+            )
+            .await
+        })?;
+
+        let span = call.head;
+        let value = Value::record(
+            record! {
+                "inserted" => Value::int(summary.inserted as i64, span),
+                "updated" => Value::int(summary.updated as i64, span),
+                "deleted" => Value::int(summary.deleted as i64, span),
+            },
+            span,
+        );
+
+        Ok(value.into_pipeline_data())
+    }
+}
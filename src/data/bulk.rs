@@ -0,0 +1,238 @@
+use async_std::net::TcpStream;
+use nu_protocol::{LabeledError, Value};
+use tiberius::{Client, ColumnData, ToSql, TokenRow};
+
+use super::{quote_identifier, quote_name, table_columns, to_row, SqlParam, TableColumn};
+
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// SQL Server rejects statements with more than 2100 parameters.
+const MAX_PARAMETERS: usize = 2100;
+
+/// SQL Server rejects table value constructors with more than 1000 rows.
+pub const MAX_VALUES_ROWS: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    pub batch_size: usize,
+    pub keep_identity: bool,
+    pub tablock: bool,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            keep_identity: false,
+            tablock: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BulkSummary {
+    pub rows: u64,
+    pub batches: u64,
+}
+
+/// Loads `rows` into `table`, converting each record to the table's column types.
+///
+/// Rows are sent through the TDS bulk load protocol. Identity columns cannot be
+/// bulk loaded, so `keep_identity` falls back to batched `INSERT` statements run
+/// with `IDENTITY_INSERT` enabled. So do tables with `money` or `smallmoney`
+/// columns, which tiberius cannot bulk load.
+#[allow(clippy::result_large_err)]
+pub async fn bulk_insert(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    rows: impl Iterator<Item = Value>,
+    options: &BulkOptions,
+) -> Result<BulkSummary, LabeledError> {
+    let columns = insert_columns(client, table, options).await?;
+    let rows = rows.map(|value| to_row(&value, &columns));
+    bulk_insert_rows(client, table, &columns, rows, options).await
+}
+
+/// The columns of `table` that a load sends values for, in table order.
+pub async fn insert_columns(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    options: &BulkOptions,
+) -> Result<Vec<TableColumn>, LabeledError> {
+    Ok(table_columns(client, table)
+        .await?
+        .into_iter()
+        .filter(|column| column.is_writable() || options.keep_identity && column.identity)
+        .collect())
+}
+
+/// Loads rows already converted to the [`insert_columns`] of `table`, stopping
+/// at the first error.
+pub async fn bulk_insert_rows(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    columns: &[TableColumn],
+    rows: impl Iterator<Item = Result<Vec<ColumnData<'static>>, LabeledError>>,
+    options: &BulkOptions,
+) -> Result<BulkSummary, LabeledError> {
+    let table = &quote_name(table);
+    if options.tablock {
+        // Hold an exclusive table lock for the whole load, released on commit.
+        let lock =
+            format!("BEGIN TRANSACTION; SELECT TOP 0 NULL FROM {table} WITH (TABLOCKX, HOLDLOCK)");
+        run_batch(client, &lock).await?;
+    }
+
+    let money = |column: &TableColumn| matches!(column.sql_type.as_str(), "money" | "smallmoney");
+    let result = if options.keep_identity || columns.iter().any(money) {
+        insert_values(client, table, columns, rows, options).await
+    } else {
+        bulk_load(client, table, columns, rows, options).await
+    };
+
+    if options.tablock {
+        match result {
+            Ok(_) => run_batch(client, "COMMIT TRANSACTION").await?,
+            // The load error explains the failure better than a failed rollback would.
+            Err(_) => {
+                let _ = run_batch(client, "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION").await;
+            }
+        }
+    }
+
+    result
+}
+
+async fn bulk_load(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    columns: &[TableColumn],
+    rows: impl Iterator<Item = Result<Vec<ColumnData<'static>>, LabeledError>>,
+    options: &BulkOptions,
+) -> Result<BulkSummary, LabeledError> {
+    let mut summary = BulkSummary::default();
+    let mut rows = rows.peekable();
+
+    while rows.peek().is_some() {
+        // Convert the whole batch first, an error while the bulk load request
+        // is open would leave the connection inside it.
+        let batch = rows
+            .by_ref()
+            .take(options.batch_size)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut request = client
+            .bulk_insert(table)
+            .await
+            .map_err(|e| LabeledError::new(format!("Error starting bulk load: {e}")))?;
+
+        for cells in batch {
+            let mut row = TokenRow::with_capacity(columns.len());
+            for cell in cells {
+                row.push(cell);
+            }
+            request
+                .send(row)
+                .await
+                .map_err(|e| LabeledError::new(format!("Error sending row: {e}")))?;
+        }
+
+        let result = request
+            .finalize()
+            .await
+            .map_err(|e| LabeledError::new(format!("Error finishing bulk load: {e}")))?;
+
+        summary.rows += result.total();
+        summary.batches += 1;
+    }
+
+    Ok(summary)
+}
+
+async fn insert_values(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    columns: &[TableColumn],
+    rows: impl Iterator<Item = Result<Vec<ColumnData<'static>>, LabeledError>>,
+    options: &BulkOptions,
+) -> Result<BulkSummary, LabeledError> {
+    let has_identity = columns.iter().any(|column| column.identity);
+    if has_identity {
+        run_batch(client, &format!("SET IDENTITY_INSERT {table} ON")).await?;
+    }
+
+    let rows_per_statement = (MAX_PARAMETERS - 1)
+        .checked_div(columns.len())
+        .unwrap_or(1)
+        .clamp(1, MAX_VALUES_ROWS)
+        .min(options.batch_size);
+
+    let column_list = columns
+        .iter()
+        .map(|column| quote_identifier(&column.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut summary = BulkSummary::default();
+    let mut rows = rows.peekable();
+    let mut result = Ok(());
+
+    while rows.peek().is_some() && result.is_ok() {
+        let mut cells: Vec<ColumnData<'static>> = vec![];
+        for row in rows.by_ref().take(rows_per_statement) {
+            match row {
+                Ok(row) => cells.extend(row),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if result.is_err() {
+            break;
+        }
+
+        let row_count = cells.len() / columns.len().max(1);
+        let values = (0..row_count)
+            .map(|row| {
+                let params = (0..columns.len())
+                    .map(|col| format!("@P{}", row * columns.len() + col + 1))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("({params})")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let sql = format!("INSERT INTO {table} ({column_list}) VALUES {values}");
+        let params: Vec<SqlParam> = cells.into_iter().map(SqlParam).collect();
+        let params: Vec<&dyn ToSql> = params.iter().map(|p| p as &dyn ToSql).collect();
+
+        match client.execute(sql, &params).await {
+            Ok(done) => {
+                summary.rows += done.total();
+                summary.batches += 1;
+            }
+            Err(e) => result = Err(LabeledError::new(format!("Error inserting rows: {e}"))),
+        }
+    }
+
+    if has_identity {
+        let identity_off = run_batch(client, &format!("SET IDENTITY_INSERT {table} OFF")).await;
+        result = result.and(identity_off);
+    }
+
+    result.map(|_| summary)
+}
+
+/// Runs a batch of statements whose results are not needed.
+pub async fn run_batch(client: &mut Client<TcpStream>, sql: &str) -> Result<(), LabeledError> {
+    client
+        .simple_query(sql)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error running {sql}: {e}")))?
+        .into_results()
+        .await
+        .map_err(|e| LabeledError::new(format!("Error running {sql}: {e}")))?;
+    Ok(())
+}
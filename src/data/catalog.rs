@@ -0,0 +1,226 @@
+use async_std::{net::TcpStream, stream::StreamExt};
+use nu_protocol::{record, LabeledError, Span, Value};
+use tiberius::{Client, Query, Row};
+
+use super::{query_records, type_declaration};
+
+/// Matches `text` against a glob pattern using `*` and `?`, ignoring case.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character and retry.
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Converts integer byte counts in `fields` of a record into filesize values.
+pub(crate) fn as_filesize(row: &mut Value, fields: &[&str]) {
+    if let Value::Record { val, .. } = row {
+        let record = val.to_mut();
+        for field in fields {
+            if let Some(value) = record.get_mut(field) {
+                if let Value::Int { val: bytes, .. } = value {
+                    *value = Value::filesize(*bytes, value.span());
+                }
+            }
+        }
+    }
+}
+
+/// Lists the databases on the server with their state, size and last backups.
+pub async fn databases(client: &mut Client<TcpStream>) -> Result<Vec<Value>, LabeledError> {
+    // Sizes are stored as a count of 8KB pages.
+    let sql = "SELECT d.name,
+            d.state_desc AS state,
+            d.recovery_model_desc AS recovery_model,
+            CAST(d.compatibility_level AS INT) AS compatibility_level,
+            d.collation_name AS collation,
+            SUSER_SNAME(d.owner_sid) AS owner,
+            CAST(SUM(CASE WHEN f.type = 0 THEN CAST(f.size AS BIGINT) END) * 8192 AS BIGINT) AS data_size,
+            CAST(SUM(CASE WHEN f.type = 1 THEN CAST(f.size AS BIGINT) END) * 8192 AS BIGINT) AS log_size,
+            MAX(b.last_full_backup) AS last_full_backup,
+            MAX(b.last_differential_backup) AS last_differential_backup,
+            MAX(b.last_log_backup) AS last_log_backup
+        FROM sys.databases d
+        LEFT JOIN sys.master_files f ON f.database_id = d.database_id
+        LEFT JOIN (
+            SELECT database_name,
+                MAX(CASE WHEN type = 'D' THEN backup_finish_date END) AS last_full_backup,
+                MAX(CASE WHEN type = 'I' THEN backup_finish_date END) AS last_differential_backup,
+                MAX(CASE WHEN type = 'L' THEN backup_finish_date END) AS last_log_backup
+            FROM msdb.dbo.backupset
+            GROUP BY database_name
+        ) b ON b.database_name = d.name
+        GROUP BY d.database_id, d.name, d.state_desc, d.recovery_model_desc,
+            d.compatibility_level, d.collation_name, d.owner_sid
+        ORDER BY d.name";
+
+    let mut rows = query_records(client, sql, &[]).await?;
+    for row in rows.iter_mut() {
+        as_filesize(row, &["data_size", "log_size"]);
+    }
+
+    Ok(rows)
+}
+
+/// Lists the tables and views in the current database with their row counts and sizes.
+///
+/// The pattern is matched against `schema.name`, or just the name when it has no dot.
+pub async fn tables(
+    client: &mut Client<TcpStream>,
+    pattern: Option<&str>,
+) -> Result<Vec<Value>, LabeledError> {
+    let sql = "SELECT s.name AS [schema], o.name,
+            CASE o.type WHEN 'U' THEN 'table' ELSE 'view' END AS type,
+            CAST((SELECT SUM(p.rows) FROM sys.partitions p
+                WHERE p.object_id = o.object_id AND p.index_id IN (0, 1)) AS BIGINT) AS row_count,
+            CAST((SELECT SUM(a.total_pages) FROM sys.partitions p
+                JOIN sys.allocation_units a ON a.container_id = p.partition_id
+                WHERE p.object_id = o.object_id) * 8192 AS BIGINT) AS size
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.type IN ('U', 'V') AND o.is_ms_shipped = 0
+        ORDER BY s.name, o.name";
+
+    let mut rows = query_records(client, sql, &[]).await?;
+    if let Some(pattern) = pattern {
+        rows.retain(|row| {
+            let field = |name: &str| {
+                row.get_data_by_key(name)
+                    .and_then(|value| value.as_str().ok().map(str::to_string))
+                    .unwrap_or_default()
+            };
+            let name = match pattern.contains('.') {
+                true => format!("{}.{}", field("schema"), field("name")),
+                false => field("name"),
+            };
+            glob_match(pattern, &name)
+        });
+    }
+    for row in rows.iter_mut() {
+        as_filesize(row, &["size"]);
+    }
+
+    Ok(rows)
+}
+
+/// Lists the columns of `table` with their full type declarations, defaults and collations.
+pub async fn columns(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    pattern: Option<&str>,
+) -> Result<Vec<Value>, LabeledError> {
+    let mut query = Query::new(
+        "SELECT c.column_id, c.name,
+            CASE WHEN t.is_user_defined = 1 AND t.is_assembly_type = 0
+                THEN TYPE_NAME(c.system_type_id) ELSE t.name END AS type_name,
+            c.max_length, c.precision, c.scale, c.is_nullable, c.is_identity, c.is_computed,
+            COALESCE(d.definition, cc.definition) AS definition, c.collation_name,
+            COALESCE(cc.is_persisted, 0) AS is_persisted
+        FROM sys.columns c
+        JOIN sys.types t ON t.user_type_id = c.user_type_id
+        LEFT JOIN sys.default_constraints d ON d.object_id = c.default_object_id
+        LEFT JOIN sys.computed_columns cc
+            ON cc.object_id = c.object_id AND cc.column_id = c.column_id
+        WHERE c.object_id = OBJECT_ID(@P1)
+        ORDER BY c.column_id",
+    );
+    query.bind(table);
+
+    let mut stream = query
+        .query(client)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error reading columns of {table}: {e}")))?
+        .into_row_stream();
+
+    let mut columns = vec![];
+    let mut exists = false;
+    while let Some(row) = stream.next().await {
+        let row = row.map_err(|e| LabeledError::new(format!("Error reading columns: {e}")))?;
+        exists = true;
+        let name = row.get::<&str, _>(1).unwrap_or_default();
+        if pattern.is_some_and(|pattern| !glob_match(pattern, name)) {
+            continue;
+        }
+        columns.push(column_record(&row));
+    }
+
+    if !exists {
+        return Err(LabeledError::new(format!("Table {table} does not exist")));
+    }
+
+    Ok(columns)
+}
+
+fn column_record(row: &Row) -> Value {
+    let span = Span::unknown();
+    let text = |index: usize| match row.get::<&str, _>(index) {
+        Some(text) => Value::string(text, span),
+        None => Value::nothing(span),
+    };
+    let flag = |index: usize| Value::bool(row.get::<bool, _>(index).unwrap_or_default(), span);
+    let computed = row.get::<bool, _>(8).unwrap_or_default();
+
+    let sql_type = type_declaration(
+        row.get::<&str, _>(2).unwrap_or_default(),
+        row.get(3).unwrap_or_default(),
+        row.get(4).unwrap_or_default(),
+        row.get(5).unwrap_or_default(),
+    );
+
+    Value::record(
+        record! {
+            "ordinal" => Value::int(row.get::<i32, _>(0).unwrap_or_default() as i64, span),
+            "name" => text(1),
+            "type" => Value::string(sql_type, span),
+            "nullable" => flag(6),
+            "identity" => flag(7),
+            "computed" => Value::bool(computed, span),
+            "computed_definition" => match computed {
+                true => text(9),
+                false => Value::nothing(span),
+            },
+            "persisted" => flag(11),
+            "default" => match computed {
+                true => Value::nothing(span),
+                false => text(9),
+            },
+            "collation" => text(10),
+        },
+        span,
+    )
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("User*", "Users"));
+    assert!(glob_match("dbo.*", "dbo.Pokemon"));
+    assert!(glob_match("*mon", "Pokemon"));
+    assert!(glob_match("?tems", "items"));
+    assert!(glob_match("*a*b*", "xaybzb"));
+    assert!(!glob_match("User", "Users"));
+    assert!(!glob_match("*x", "Pokemon"));
+}
@@ -1,10 +1,18 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use async_std::{channel::Sender, net::TcpStream, stream::StreamExt};
-use nu_protocol::{LabeledError, Record, ShellError, Span, Spanned, Value};
-use tiberius::{AuthMethod, Client};
+use async_std::{
+    channel::Sender,
+    net::TcpStream,
+    stream::StreamExt,
+    sync::{Mutex, MutexGuard},
+};
+use nu_protocol::{LabeledError, ShellError, Span, Spanned, Value};
+use tiberius::{AuthMethod, Client, QueryItem};
 
-use super::{parse_value, ConnectionArgs};
+use super::{
+    describe_result, prepare_query, result_columns, schema_value, ConnectionArgs, ParseOptions,
+    ResultColumn, ResultReader,
+};
 
 #[derive(Debug, Clone)]
 pub struct Connection {
@@ -20,11 +28,16 @@ impl Connection {
         }
     }
 
+    /// Locks the underlying client for the duration of the returned guard.
+    pub async fn client(&self) -> MutexGuard<'_, Client<TcpStream>> {
+        self.connection.lock().await
+    }
+
     pub async fn close(&self) {
         let client_arc = self.connection.clone();
         let client_mutex = Arc::try_unwrap(client_arc).ok();
         if let Some(client) = client_mutex {
-            let client = client.into_inner().unwrap();
+            let client = client.into_inner();
             match client.close().await {
                 Ok(_) => {
                     eprintln!("Connection: Closed connection");
@@ -36,52 +49,120 @@ impl Connection {
         }
     }
 
-    pub async fn run_query<'a>(&self, query: Spanned<String>, sender: Sender<Value>) {
-        let client_arc = self.connection.clone();
-        let client_mutex = Arc::try_unwrap(client_arc).ok();
-        if let Some(client) = client_mutex {
-            let mut client = client.into_inner().unwrap();
+    pub async fn run_query<'a>(
+        &self,
+        query: Spanned<String>,
+        mut options: ParseOptions,
+        sender: Sender<Value>,
+    ) {
+        let mut client = self.client().await;
 
-            let stream = match client.simple_query(query.item).await {
-                Ok(stream) => stream,
-                Err(e) => {
-                    panic!("Error: {}", e);
-                }
-            };
+        let described = describe_result(&mut client, &query.item).await;
+        let sql = match prepare_query(&mut client, &query.item, described.as_deref(), &mut options)
+            .await
+        {
+            Ok(sql) => sql,
+            Err(e) => {
+                let error = Value::error(ShellError::LabeledError(Box::new(e)), query.span);
+                send_all(&sender, vec![error]).await;
+                return;
+            }
+        };
 
-            let mut row_stream = stream.into_row_stream();
-            while let Some(row) = row_stream.next().await {
-                match row {
-                    Ok(row) => {
-                        let mut record = Record::new();
-
-                        for (col, cell) in row.cells() {
-                            match parse_value(cell) {
-                                Ok(value) => {
-                                    record.insert(col.name(), value);
-                                }
-                                Err(e) => {
-                                    panic!("Error: {:?}", e);
-                                }
-                            }
-                        }
+        let error = |e: tiberius::error::Error, message: &str| {
+            let e = LabeledError::new(format!("{message}: {e}")).with_label("failed", query.span);
+            Value::error(ShellError::LabeledError(Box::new(e)), query.span)
+        };
 
-                        if let Err(e) = sender.send(Value::record(record, Span::unknown())).await {
-                            if sender.is_closed() {
-                                return;
+        let mut stream = match client.simple_query(sql).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                send_all(&sender, vec![error(e, "Error running query")]).await;
+                return;
+            }
+        };
+
+        let duplicate_columns = options.duplicate_columns;
+        let mut reader = ResultReader::new(options);
+        let mut result_sets = 0;
+        // The columns of the first result set, until it turns out to have rows.
+        let mut empty: Option<Vec<ResultColumn>> = None;
+        let schema = |empty: Option<Vec<ResultColumn>>, values: Vec<Value>| match empty {
+            Some(columns) if values.is_empty() => {
+                vec![schema_value(columns, vec![], duplicate_columns, query.span)]
+            }
+            _ => values,
+        };
+        loop {
+            let values = match stream.next().await {
+                Some(Ok(QueryItem::Metadata(metadata))) => {
+                    match reader.metadata(metadata.columns()) {
+                        Ok(values) => {
+                            result_sets += 1;
+                            if result_sets == 1 {
+                                let columns = metadata.columns();
+                                empty = Some(result_columns(
+                                    columns,
+                                    reader.names(),
+                                    described.as_deref(),
+                                ));
+                                Ok(values)
+                            } else {
+                                Ok(schema(empty.take(), values))
                             }
-                            panic!("Error: {:?}", e);
+                        }
+                        // The rows that follow cannot be named, so the stream ends here.
+                        Err(e) => {
+                            let error = ShellError::LabeledError(Box::new(e));
+                            send_all(&sender, vec![Value::error(error, query.span)]).await;
+                            return;
                         }
                     }
-                    Err(e) => {
-                        panic!("Error: {}", e);
+                }
+                Some(Ok(QueryItem::Row(row))) => reader.row(&row).inspect(|values| {
+                    if !values.is_empty() {
+                        empty = None;
                     }
+                }),
+                Some(Err(e)) => {
+                    send_all(&sender, vec![error(e, "Error reading results")]).await;
+                    return;
                 }
+                None => break,
+            };
+
+            let values = match values {
+                Ok(values) => values,
+                Err(e) => vec![Value::error(
+                    ShellError::LabeledError(Box::new(e)),
+                    query.span,
+                )],
+            };
+            if !send_all(&sender, values).await {
+                return;
             }
-        } else {
-            eprintln!("Failed to get connection reference");
+        }
+
+        let values = match reader.finish() {
+            Ok(values) => schema(empty, values),
+            Err(e) => vec![Value::error(
+                ShellError::LabeledError(Box::new(e)),
+                query.span,
+            )],
+        };
+        send_all(&sender, values).await;
+    }
+}
+
+/// Sends `values` down the pipeline, returning false once it has been closed.
+async fn send_all(sender: &Sender<Value>, values: Vec<Value>) -> bool {
+    for value in values {
+        // Sending only fails once the receiving end has been dropped.
+        if sender.send(value).await.is_err() {
+            return false;
         }
     }
+    true
 }
 
 #[derive(Debug)]
@@ -1,6 +1,6 @@
 
 use std::hash::{Hash, Hasher};
-use nu_protocol::{LabeledError, Span, Value};
+use nu_protocol::{LabeledError, Signature, Span, SyntaxShape, Value};
 use serde::{Deserialize, Serialize};
 
 use crate::DEFAULT_BUFFER_SIZE;
@@ -96,7 +96,89 @@ impl ConnectionArgs {
         Ok(args)
     }
     
+    /// Returns a copy of these arguments with the fields of `overrides` applied.
+    ///
+    /// The record takes the same names as the connection flags, with `trust_cert`
+    /// as a boolean, so a command can connect to a second server or database.
+    #[allow(clippy::result_large_err)]
+    pub fn with_overrides(&self, overrides: &Value) -> Result<ConnectionArgs, LabeledError> {
+        let record = overrides.as_record().map_err(|_| {
+            LabeledError::new("Expected a record of connection arguments")
+                .with_label("not a record", overrides.span())
+        })?;
+
+        let mut args = self.clone();
+        args.reference_count = 0;
+        for (name, value) in record.iter() {
+            match name.as_str() {
+                "server" => args.server = Some(value.clone()),
+                "instance" => args.instance = Some(value.clone()),
+                "database" => args.database = Some(value.clone()),
+                "user" => args.user = Some(value.clone()),
+                "password" => args.password = Some(value.clone()),
+                "trust_cert" | "trust-cert" => {
+                    args.trust_cert = match value.as_bool() {
+                        Ok(true) => Some(value.span()),
+                        _ => None,
+                    }
+                }
+                other => {
+                    return Err(LabeledError::new(format!(
+                        "Unknown connection argument {other}"
+                    ))
+                    .with_label(
+                        "expected server, instance, database, user, password or trust_cert",
+                        value.span(),
+                    ))
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
     pub(crate) fn as_ref(&self) -> &ConnectionArgs {
         self
     }
-}
\ No newline at end of file
+}
+
+/// Adds the flags read by [`ConnectionArgs::from_call`] to a command signature.
+pub trait ConnectionFlags {
+    fn connection_flags(self) -> Self;
+}
+
+impl ConnectionFlags for Signature {
+    fn connection_flags(self) -> Self {
+        self.named(
+            "server",
+            SyntaxShape::String,
+            "The server to connect to, default: localhost",
+            Some('s'),
+        )
+        .named(
+            "instance",
+            SyntaxShape::String,
+            "The server instance to connect to",
+            Some('i'),
+        )
+        .named(
+            "database",
+            SyntaxShape::String,
+            "The database to connect to, default: master",
+            Some('d'),
+        )
+        .named(
+            "user",
+            SyntaxShape::String,
+            "The user to connect as, default: sa",
+            Some('u'),
+        )
+        .named(
+            "password",
+            SyntaxShape::String,
+            "The password to connect with",
+            Some('p'),
+        )
+        .switch("trust-cert", "Trust the server certificate", Some('t'))
+    }
+}
@@ -3,8 +3,8 @@ use std::{
     sync::{Arc, Mutex, MutexGuard},
 };
 
-use async_std::net::TcpStream;
-use nu_protocol::{LabeledError, ShellError, Value};
+use async_std::{net::TcpStream, sync::Mutex as AsyncMutex};
+use nu_protocol::{LabeledError, PipelineData, ShellError, Value};
 use tiberius::{error::Error, AuthMethod, Client, Config, SqlBrowser};
 
 use super::{Connection, ConnectionArgs, ConnectionError};
@@ -41,24 +41,7 @@ impl ConnectionPool {
     ) -> anyhow::Result<Connection, ShellError> {
         eprintln!("Connection pool: Creating connection");
 
-        let config = match config_from_args(&args) {
-            Ok(config) => config,
-            Err(e) => return Err(e.to_shell_error(&args)),
-        };
-
-        let stream = match create_stream(&args, &config).await {
-            Ok(stream) => stream,
-            Err(e) => return Err(ConnectionError::SetupError(e).to_shell_error(&args)),
-        };
-    
-        let connection = match Client::connect(config, stream).await {
-            Ok(client) => Connection::new(Arc::new(Mutex::new(client))),
-            Err(Error::Server(e)) if e.code() == 18456 => {
-                let auth = get_auth_method(&args).unwrap();
-                return Err(ConnectionError::LoginFailed(auth).to_shell_error(&args))
-            }
-            Err(e) => return Err(ConnectionError::ConnectionError(e).to_shell_error(&args)),
-        };
+        let connection = connect(&args).await?;
 
         let mut lock = self.lock()?;
         let _ = lock.insert(args, connection.clone());
@@ -82,6 +65,43 @@ impl ConnectionPool {
         Ok(result)
     }
 
+    /// Returns the pooled connection for `args`, connecting first if the pool has none.
+    pub async fn get_or_create(
+        &self,
+        engine: &nu_plugin::EngineInterface,
+        args: ConnectionArgs,
+    ) -> Result<Connection, ShellError> {
+        match self.get(&args, true)? {
+            Some(connection) => {
+                eprintln!("Connection pool returned existing connection");
+                Ok(connection)
+            }
+            None => {
+                let connection = self.create_connection(engine, args).await?;
+                eprintln!("Connection pool created new connection");
+                Ok(connection)
+            }
+        }
+    }
+
+    /// Returns a connection for a command that writes its input to the
+    /// database as it reads it.
+    ///
+    /// A streamed input may come from a query still holding the pooled
+    /// connection, e.g. `mssql query | mssql insert`, so the command gets a
+    /// connection of its own, closed when it is dropped.
+    pub async fn get_for_input(
+        &self,
+        engine: &nu_plugin::EngineInterface,
+        args: ConnectionArgs,
+        input: &PipelineData,
+    ) -> Result<Connection, ShellError> {
+        match input {
+            PipelineData::ListStream(..) | PipelineData::ByteStream(..) => connect(&args).await,
+            _ => self.get_or_create(engine, args).await,
+        }
+    }
+
     pub async fn close(&self) {
         for connection in self.connections.lock().unwrap().values_mut() {
             connection.close().await;
@@ -89,6 +109,28 @@ impl ConnectionPool {
     }
 }
 
+/// Opens a new connection that is not added to the pool.
+async fn connect(args: &ConnectionArgs) -> Result<Connection, ShellError> {
+    let config = match config_from_args(args) {
+        Ok(config) => config,
+        Err(e) => return Err(e.to_shell_error(args)),
+    };
+
+    let stream = match create_stream(args, &config).await {
+        Ok(stream) => stream,
+        Err(e) => return Err(ConnectionError::SetupError(e).to_shell_error(args)),
+    };
+
+    match Client::connect(config, stream).await {
+        Ok(client) => Ok(Connection::new(Arc::new(AsyncMutex::new(client)))),
+        Err(Error::Server(e)) if e.code() == 18456 => {
+            let auth = get_auth_method(args).unwrap();
+            Err(ConnectionError::LoginFailed(auth).to_shell_error(args))
+        }
+        Err(e) => Err(ConnectionError::ConnectionError(e).to_shell_error(args)),
+    }
+}
+
 pub async fn create_stream(
     args: &ConnectionArgs,
     config: &Config,
@@ -0,0 +1,261 @@
+use nu_protocol::{LabeledError, Value};
+
+use super::{quote_identifier, quote_name, to_json};
+
+/// NVARCHAR columns longer than this must be declared as NVARCHAR(MAX).
+const MAX_NVARCHAR_LENGTH: usize = 4000;
+
+/// VARBINARY columns longer than this must be declared as VARBINARY(MAX).
+const MAX_VARBINARY_LENGTH: usize = 8000;
+
+/// The SQL type inferred for a column from the values seen so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InferredType {
+    Unknown,
+    Bit,
+    Int,
+    BigInt,
+    Float,
+    DateTimeOffset,
+    NVarChar(usize),
+    VarBinary(usize),
+}
+
+impl InferredType {
+    #[allow(clippy::result_large_err)]
+    fn from_value(value: &Value) -> Result<Option<Self>, LabeledError> {
+        let inferred = match value {
+            Value::Nothing { .. } => return Ok(None),
+            Value::Bool { .. } => Self::Bit,
+            Value::Int { val, .. } if i32::try_from(*val).is_ok() => Self::Int,
+            Value::Int { .. } | Value::Filesize { .. } | Value::Duration { .. } => Self::BigInt,
+            Value::Float { .. } => Self::Float,
+            Value::Date { .. } => Self::DateTimeOffset,
+            Value::Binary { val, .. } => Self::VarBinary(val.len()),
+            // NVARCHAR lengths count UTF-16 code units.
+            Value::String { val, .. } => Self::NVarChar(val.encode_utf16().count()),
+            // Records and lists are stored as JSON text.
+            Value::Record { .. } | Value::List { .. } => {
+                Self::NVarChar(to_json(value.clone()).to_string().encode_utf16().count())
+            }
+            Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+            other => {
+                return Err(LabeledError::new(format!(
+                    "Cannot store {} in a column",
+                    other.get_type()
+                ))
+                .with_label("unsupported value", other.span()))
+            }
+        };
+        Ok(Some(inferred))
+    }
+
+    /// Widens two inferred types to one that can hold values of both.
+    fn merge(self, other: Self) -> Self {
+        use InferredType::*;
+        match (self, other) {
+            (Unknown, other) | (other, Unknown) => other,
+            (a, b) if a == b => a,
+            (Int, BigInt) | (BigInt, Int) => BigInt,
+            (Int | BigInt, Float) | (Float, Int | BigInt) => Float,
+            (NVarChar(a), NVarChar(b)) => NVarChar(a.max(b)),
+            (VarBinary(a), VarBinary(b)) => VarBinary(a.max(b)),
+            (a, b) => NVarChar(a.text_length().max(b.text_length())),
+        }
+    }
+
+    /// The length needed to hold any value of this type as text.
+    fn text_length(self) -> usize {
+        match self {
+            Self::Unknown => 1,
+            Self::Bit => 5,
+            Self::Int => 11,
+            Self::BigInt => 20,
+            Self::Float => 24,
+            Self::DateTimeOffset => 34,
+            Self::NVarChar(length) => length,
+            Self::VarBinary(_) => MAX_NVARCHAR_LENGTH + 1,
+        }
+    }
+
+    fn to_sql(self) -> String {
+        match self {
+            Self::Bit => "BIT".into(),
+            Self::Int => "INT".into(),
+            Self::BigInt => "BIGINT".into(),
+            Self::Float => "FLOAT".into(),
+            Self::DateTimeOffset => "DATETIMEOFFSET".into(),
+            Self::NVarChar(length) if length > MAX_NVARCHAR_LENGTH => "NVARCHAR(MAX)".into(),
+            Self::NVarChar(length) => format!("NVARCHAR({})", length.max(1)),
+            Self::VarBinary(length) if length > MAX_VARBINARY_LENGTH => "VARBINARY(MAX)".into(),
+            Self::VarBinary(length) => format!("VARBINARY({})", length.max(1)),
+            // Columns that only ever held nothing.
+            Self::Unknown => "NVARCHAR(1)".into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InferredColumn {
+    name: String,
+    sql_type: InferredType,
+    nullable: bool,
+    rows: usize,
+}
+
+/// Builds a `CREATE TABLE` statement for `table` from the values in `rows`.
+///
+/// Columns appear in the order their fields are first seen. A column is
+/// nullable when any row holds nothing for it or leaves it out.
+#[allow(clippy::result_large_err)]
+pub fn create_table_sql(
+    table: &str,
+    rows: &[Value],
+    primary_key: &[String],
+) -> Result<String, LabeledError> {
+    let mut columns: Vec<InferredColumn> = vec![];
+
+    for row in rows {
+        let record = match row {
+            Value::Record { val, .. } => val,
+            Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+            other => {
+                return Err(LabeledError::new(format!(
+                    "Expected a table but got {}",
+                    other.get_type()
+                ))
+                .with_label("not a record", other.span()))
+            }
+        };
+
+        for (name, value) in record.iter() {
+            // SQL Server column names are case insensitive.
+            let index = match columns
+                .iter()
+                .position(|column| column.name.eq_ignore_ascii_case(name))
+            {
+                Some(index) => index,
+                None => {
+                    columns.push(InferredColumn {
+                        name: name.clone(),
+                        sql_type: InferredType::Unknown,
+                        nullable: false,
+                        rows: 0,
+                    });
+                    columns.len() - 1
+                }
+            };
+
+            let column = &mut columns[index];
+            column.rows += 1;
+            match InferredType::from_value(value)? {
+                Some(inferred) => column.sql_type = column.sql_type.merge(inferred),
+                None => column.nullable = true,
+            }
+        }
+    }
+
+    if columns.is_empty() {
+        return Err(LabeledError::new("Cannot infer a table from empty input"));
+    }
+
+    for column in columns.iter_mut() {
+        if column.rows < rows.len() {
+            column.nullable = true;
+        }
+    }
+
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "    {} {} {}",
+                quote_identifier(&column.name),
+                column.sql_type.to_sql(),
+                if column.nullable { "NULL" } else { "NOT NULL" }
+            )
+        })
+        .collect();
+
+    if !primary_key.is_empty() {
+        for key in primary_key {
+            match columns
+                .iter()
+                .find(|column| column.name.eq_ignore_ascii_case(key))
+            {
+                Some(column) if column.nullable => {
+                    return Err(LabeledError::new(format!(
+                        "Primary key column {key} contains empty values"
+                    )))
+                }
+                Some(_) => {}
+                None => {
+                    return Err(LabeledError::new(format!(
+                        "Primary key column {key} is not in the input"
+                    )))
+                }
+            }
+        }
+
+        let keys = primary_key
+            .iter()
+            .map(|key| quote_identifier(key))
+            .collect::<Vec<_>>()
+            .join(", ");
+        definitions.push(format!("    PRIMARY KEY ({keys})"));
+    }
+
+    Ok(format!(
+        "CREATE TABLE {} (\n{}\n);",
+        quote_name(table),
+        definitions.join(",\n")
+    ))
+}
+
+#[test]
+fn test_create_table_sql() {
+    use nu_protocol::{record, Span};
+
+    let span = Span::unknown();
+    let rows = vec![
+        Value::record(
+            record! {
+                "id" => Value::int(1, span),
+                "name" => Value::string("Ash", span),
+                "score" => Value::int(10, span),
+            },
+            span,
+        ),
+        Value::record(
+            record! {
+                "id" => Value::int(5_000_000_000, span),
+                "name" => Value::string("Misty", span),
+                "score" => Value::float(2.5, span),
+                "active" => Value::bool(true, span),
+            },
+            span,
+        ),
+    ];
+
+    let sql = create_table_sql("dbo.Trainers", &rows, &["id".into()]).unwrap();
+    assert_eq!(
+        sql,
+        "CREATE TABLE [dbo].[Trainers] (
+    [id] BIGINT NOT NULL,
+    [name] NVARCHAR(5) NOT NULL,
+    [score] FLOAT NOT NULL,
+    [active] BIT NULL,
+    PRIMARY KEY ([id])
+);"
+    );
+
+    let rows = vec![
+        Value::record(record! { "Note" => Value::string("\u{1F600}", span) }, span),
+        Value::record(record! { "note" => Value::duration(5, span) }, span),
+    ];
+    let sql = create_table_sql("Notes", &rows, &[]).unwrap();
+    assert_eq!(
+        sql,
+        "CREATE TABLE [Notes] (\n    [Note] NVARCHAR(20) NOT NULL\n);"
+    );
+}
@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use async_std::{net::TcpStream, task};
+use nu_protocol::{record, LabeledError, Record, ShellError, Span, Value};
+use tiberius::Client;
+
+use super::{keyword_positions, query_records, quote_identifier, quote_name, Connection};
+
+pub const DEFAULT_DIFF_CHUNK_SIZE: usize = 10_000;
+
+/// A table name or a query whose rows are compared.
+#[derive(Debug, Clone)]
+pub enum DiffSource {
+    Table(String),
+    /// A query, split into the `WITH` clause of its common table expressions,
+    /// empty when it has none, and the `SELECT` that follows it.
+    Query {
+        with: String,
+        select: String,
+    },
+}
+
+impl DiffSource {
+    /// Treats text starting with `SELECT` or `WITH` as a query and anything
+    /// else as a table name, so `[Order Details]` is read as a table.
+    #[allow(clippy::result_large_err)]
+    pub fn parse(text: &str) -> Result<Self, LabeledError> {
+        let text = text.trim();
+        let start = text.trim_start_matches('(');
+        if starts_with_keyword(start, "SELECT") {
+            return Ok(DiffSource::Query {
+                with: String::new(),
+                select: text.to_string(),
+            });
+        }
+        if !starts_with_keyword(start, "WITH") {
+            return Ok(DiffSource::Table(text.to_string()));
+        }
+
+        // A derived table cannot hold common table expressions, so they are
+        // kept in front of the statements and only the final SELECT is wrapped.
+        let select = keyword_positions(text, "SELECT").first().copied()
+            .ok_or_else(|| LabeledError::new("Expected the WITH query to end with a SELECT"))?;
+        Ok(DiffSource::Query {
+            with: text[..select].trim_end().to_string(),
+            select: text[select..].to_string(),
+        })
+    }
+
+    /// Builds a statement reading from the source, with `select` given the
+    /// source as a relation named `x`.
+    fn statement(&self, select: impl FnOnce(&str) -> String) -> String {
+        match self {
+            DiffSource::Table(table) => select(&format!("{} AS x", quote_name(table))),
+            DiffSource::Query {
+                with,
+                select: query,
+            } if with.is_empty() => select(&format!("({query}) AS x")),
+            DiffSource::Query {
+                with,
+                select: query,
+            } => {
+                format!("{with}\n{}", select(&format!("({query}) AS x")))
+            }
+        }
+    }
+}
+
+fn starts_with_keyword(text: &str, keyword: &str) -> bool {
+    text.get(..keyword.len())
+        .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+        && text[keyword.len()..].starts_with(|c: char| c.is_whitespace() || c == '(' || c == '*')
+}
+
+/// One side of a comparison: a connection and the rows read through it.
+#[derive(Debug, Clone)]
+pub struct DiffSide {
+    pub connection: Connection,
+    pub source: DiffSource,
+}
+
+/// Streams the differences between the rows of two sources, matched by key.
+///
+/// Rows are spread over buckets by a checksum of their key and each bucket is
+/// hashed on the server. Only buckets whose row count or hash differ are read
+/// back and compared row by row, one bucket at a time. Both sides must have
+/// the same columns with the same types for their hashes to match. Keys are
+/// bucketed by a hash of their text form, so they need not share a type or
+/// collation, but keys whose text differs, e.g. decimals of different scales
+/// or `datetime` and `datetime2`, land in different buckets.
+///
+/// Rows only in the source are `added` and rows only in the target `removed`,
+/// matching `mssql schema-diff`. Each difference lists the affected columns
+/// with their `old` (target) and `new` (source) values.
+pub struct DataDiffStream {
+    source: DiffSide,
+    target: DiffSide,
+    keys: Vec<String>,
+    bucket_count: u64,
+    buckets: VecDeque<i64>,
+    pending: VecDeque<Value>,
+}
+
+impl DataDiffStream {
+    pub async fn new(
+        source: DiffSide,
+        target: DiffSide,
+        keys: Vec<String>,
+        chunk_size: usize,
+    ) -> Result<Self, LabeledError> {
+        if keys.is_empty() {
+            return Err(LabeledError::new("At least one key column is required"));
+        }
+
+        let mut diff = DataDiffStream {
+            source,
+            target,
+            keys,
+            bucket_count: 1,
+            buckets: VecDeque::new(),
+            pending: VecDeque::new(),
+        };
+
+        // The client of each side is released before the other is locked, as both
+        // sides may share a pooled connection.
+        let source_rows =
+            row_count(&mut *diff.source.connection.client().await, &diff.source).await?;
+        let target_rows =
+            row_count(&mut *diff.target.connection.client().await, &diff.target).await?;
+        diff.bucket_count = source_rows
+            .max(target_rows)
+            .div_ceil(chunk_size.max(1) as u64)
+            .max(1);
+
+        let source_hashes = diff
+            .bucket_hashes(&mut *diff.source.connection.client().await, &diff.source)
+            .await?;
+        let target_hashes = diff
+            .bucket_hashes(&mut *diff.target.connection.client().await, &diff.target)
+            .await?;
+
+        let mut buckets: Vec<i64> = source_hashes
+            .keys()
+            .chain(target_hashes.keys())
+            .copied()
+            .collect();
+        buckets.sort();
+        buckets.dedup();
+        diff.buckets = buckets
+            .into_iter()
+            .filter(|bucket| source_hashes.get(bucket) != target_hashes.get(bucket))
+            .collect();
+
+        Ok(diff)
+    }
+
+    fn bucket_expression(&self) -> String {
+        let keys = self
+            .keys
+            .iter()
+            .map(|key| format!("CONVERT(NVARCHAR(MAX), x.{})", quote_identifier(key)))
+            .collect::<Vec<_>>()
+            .join(", NCHAR(31), ");
+        // CHECKSUM depends on the type and collation of the keys, so the
+        // same key could fall into different buckets on the two servers.
+        format!(
+            "ABS(CAST(CAST(HASHBYTES('SHA2_256', CONCAT({keys}, N'')) AS BINARY(8)) AS BIGINT) % {})",
+            self.bucket_count
+        )
+    }
+
+    async fn bucket_hashes(
+        &self,
+        client: &mut Client<TcpStream>,
+        side: &DiffSide,
+    ) -> Result<HashMap<i64, (i64, i64)>, LabeledError> {
+        // Aggregates cannot contain subqueries, so rows are hashed in a derived table.
+        let sql = side.source.statement(|relation| {
+            format!(
+                "SELECT bucket, COUNT_BIG(*) AS row_count, CAST(CHECKSUM_AGG(row_hash) AS BIGINT) AS hash
+                FROM (
+                    SELECT {} AS bucket,
+                        CHECKSUM(HASHBYTES('SHA2_256', (SELECT x.* FOR JSON PATH,
+                            WITHOUT_ARRAY_WRAPPER, INCLUDE_NULL_VALUES))) AS row_hash
+                    FROM {relation}
+                ) AS h
+                GROUP BY bucket",
+                self.bucket_expression()
+            )
+        });
+
+        let rows = query_records(client, &sql, &[]).await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let int = |name: &str| {
+                    row.get_data_by_key(name)
+                        .and_then(|value| value.as_int().ok())
+                        .unwrap_or_default()
+                };
+                (int("bucket"), (int("row_count"), int("hash")))
+            })
+            .collect())
+    }
+
+    async fn bucket_rows(&self, side: &DiffSide, bucket: i64) -> Result<Vec<Value>, LabeledError> {
+        let sql = side.source.statement(|relation| {
+            format!(
+                "SELECT x.* FROM {relation} WHERE {} = @P1",
+                self.bucket_expression()
+            )
+        });
+        let mut client = side.connection.client().await;
+        query_records(&mut client, &sql, &[&bucket]).await
+    }
+
+    async fn diff_bucket(&self, bucket: i64) -> Result<Vec<Value>, LabeledError> {
+        let source_rows = self.bucket_rows(&self.source, bucket).await?;
+        let target_rows = self.bucket_rows(&self.target, bucket).await?;
+
+        let mut targets: BTreeMap<String, Record> = BTreeMap::new();
+        for row in target_rows {
+            let (key, _) = self.key_of(&row)?;
+            if targets.insert(key.clone(), into_record(row)).is_some() {
+                return Err(duplicate_key("target", &key));
+            }
+        }
+
+        let span = Span::unknown();
+        let mut differences = vec![];
+        let mut sources = HashSet::new();
+        for row in source_rows {
+            let (key, key_record) = self.key_of(&row)?;
+            if !sources.insert(key.clone()) {
+                return Err(duplicate_key("source", &key));
+            }
+            let new = into_record(row);
+
+            let Some(old) = targets.remove(&key) else {
+                differences.push(difference("added", key_record, &Record::new(), &new));
+                continue;
+            };
+
+            let difference = difference("changed", key_record, &old, &new);
+            let changed = difference
+                .get_data_by_key("columns")
+                .and_then(|columns| columns.as_record().ok().map(|record| !record.is_empty()))
+                .unwrap_or_default();
+            if changed {
+                differences.push(difference);
+            }
+        }
+
+        for (_, old) in targets {
+            let (_, key_record) = self.key_of(&Value::record(old.clone(), span))?;
+            differences.push(difference("removed", key_record, &old, &Record::new()));
+        }
+
+        Ok(differences)
+    }
+
+    /// Returns a comparable string for the row's key along with the key as a record.
+    #[allow(clippy::result_large_err)]
+    fn key_of(&self, row: &Value) -> Result<(String, Record), LabeledError> {
+        let record = row
+            .as_record()
+            .map_err(|_| LabeledError::new("Expected the rows to be records"))?;
+
+        let mut text = vec![];
+        let mut key = Record::new();
+        for name in &self.keys {
+            let (column, value) = record
+                .iter()
+                .find(|(column, _)| column.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    LabeledError::new(format!("Key column {name} is not in the rows"))
+                })?;
+            text.push(value.to_debug_string());
+            key.push(column, value.clone());
+        }
+
+        Ok((text.join("\u{1f}"), key))
+    }
+}
+
+impl Iterator for DataDiffStream {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Some(value);
+            }
+
+            let bucket = self.buckets.pop_front()?;
+            match task::block_on(self.diff_bucket(bucket)) {
+                Ok(differences) => self.pending.extend(differences),
+                Err(e) => {
+                    self.buckets.clear();
+                    return Some(Value::error(
+                        ShellError::LabeledError(Box::new(e)),
+                        Span::unknown(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+async fn row_count(client: &mut Client<TcpStream>, side: &DiffSide) -> Result<u64, LabeledError> {
+    let sql = side
+        .source
+        .statement(|relation| format!("SELECT COUNT_BIG(*) AS row_count FROM {relation}"));
+    let rows = query_records(client, &sql, &[]).await?;
+    Ok(rows
+        .first()
+        .and_then(|row| row.get_data_by_key("row_count"))
+        .and_then(|value| value.as_int().ok())
+        .unwrap_or_default() as u64)
+}
+
+fn duplicate_key(side: &str, key: &str) -> LabeledError {
+    LabeledError::new(format!(
+        "The key ({}) appears more than once in the {side}",
+        key.replace('\u{1f}', ", ")
+    ))
+    .with_help("the key columns must identify each row")
+}
+
+fn into_record(row: Value) -> Record {
+    match row {
+        Value::Record { val, .. } => val.into_owned(),
+        _ => Record::new(),
+    }
+}
+
+/// Builds a difference listing every column whose value differs between `old` and `new`.
+fn difference(change: &str, key: Record, old: &Record, new: &Record) -> Value {
+    let span = Span::unknown();
+    let mut columns = Record::new();
+
+    let names = new
+        .columns()
+        .chain(old.columns().filter(|name| !new.contains(name)));
+    for name in names {
+        let old = old.get(name).cloned().unwrap_or(Value::nothing(span));
+        let new = new.get(name).cloned().unwrap_or(Value::nothing(span));
+        if old != new {
+            columns.push(
+                name,
+                Value::record(record! { "old" => old, "new" => new }, span),
+            );
+        }
+    }
+
+    Value::record(
+        record! {
+            "change" => Value::string(change, span),
+            "key" => Value::record(key, span),
+            "columns" => Value::record(columns, span),
+        },
+        span,
+    )
+}
+
+#[test]
+fn test_difference() {
+    let span = Span::unknown();
+    let old = record! {
+        "id" => Value::int(1, span),
+        "name" => Value::string("a", span),
+        "size" => Value::int(2, span),
+    };
+    let new = record! {
+        "id" => Value::int(1, span),
+        "name" => Value::string("b", span),
+        "size" => Value::int(2, span),
+    };
+
+    let value = difference("changed", Record::new(), &old, &new);
+    let columns = value.get_data_by_key("columns").unwrap();
+    let columns = columns.as_record().unwrap();
+    assert_eq!(columns.columns().collect::<Vec<_>>(), vec!["name"]);
+
+    let value = difference("added", Record::new(), &Record::new(), &new);
+    let columns = value.get_data_by_key("columns").unwrap();
+    assert_eq!(columns.as_record().unwrap().len(), 3);
+}
+
+#[test]
+fn test_diff_source_parse() {
+    let is_query = |text| matches!(DiffSource::parse(text), Ok(DiffSource::Query { .. }));
+    assert!(is_query("SELECT * FROM dbo.Users"));
+    assert!(is_query("  with x AS (SELECT 1 AS id) SELECT * FROM x"));
+    assert!(is_query("(select*from t)"));
+    assert!(!is_query("dbo.Users"));
+    assert!(!is_query("[Order Details]"));
+    assert!(!is_query("dbo.[With Spaces]"));
+    assert!(!is_query("selections"));
+    assert!(!is_query("Select.Users"));
+    assert!(DiffSource::parse("WITH x AS (SELECT 1 AS id) DELETE FROM x").is_err());
+}
+
+#[test]
+fn test_diff_source_statement() {
+    let count = |text| {
+        DiffSource::parse(text)
+            .unwrap()
+            .statement(|relation| format!("SELECT COUNT_BIG(*) FROM {relation}"))
+    };
+    assert_eq!(
+        count("Order Details"),
+        "SELECT COUNT_BIG(*) FROM [Order Details] AS x"
+    );
+    assert_eq!(
+        count("SELECT id FROM t"),
+        "SELECT COUNT_BIG(*) FROM (SELECT id FROM t) AS x"
+    );
+    assert_eq!(
+        count("with a (id) AS (SELECT 1), [select] AS (SELECT ')select' AS s /* select */)\nSELECT * FROM a"),
+        "with a (id) AS (SELECT 1), [select] AS (SELECT ')select' AS s /* select */)\n\
+        SELECT COUNT_BIG(*) FROM (SELECT * FROM a) AS x"
+    );
+}
@@ -1,13 +1,99 @@
-use async_std::channel::Receiver;
-use nu_protocol::{LabeledError, Span, Value};
+use async_std::{channel::Receiver, net::TcpStream, stream::StreamExt};
+use nu_protocol::{LabeledError, Record, Span, Value};
 use tiberius::{
     time::{
         chrono::{DateTime, FixedOffset, NaiveDateTime},
         Time,
     },
-    ColumnData, FromSql,
+    Client, Column, ColumnData, FromSql, QueryItem, QueryStream, Row, ToSql,
 };
 
+/// Runs a parameterized query and returns the rows of its first result set as records.
+pub async fn query_records(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+    params: &[&dyn ToSql],
+) -> anyhow::Result<Vec<Value>, LabeledError> {
+    let rows = client
+        .query(sql, params)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error running query: {e}")))?
+        .into_first_result()
+        .await
+        .map_err(|e| LabeledError::new(format!("Error reading results: {e}")))?;
+
+    rows.iter().map(parse_row).collect()
+}
+
+/// Reads a field of a record returned by [`query_records`], nothing when it is missing.
+pub(crate) fn field(row: &Value, name: &str) -> Value {
+    row.get_data_by_key(name)
+        .unwrap_or(Value::nothing(Span::unknown()))
+}
+
+/// Reads a text field, empty when it is missing or NULL.
+pub(crate) fn text(row: &Value, name: &str) -> String {
+    match field(row, name) {
+        Value::String { val, .. } => val,
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn int(row: &Value, name: &str) -> i64 {
+    field(row, name).as_int().unwrap_or_default()
+}
+
+pub(crate) fn flag(row: &Value, name: &str) -> bool {
+    field(row, name).as_bool().unwrap_or_default()
+}
+
+pub(crate) fn list(row: &Value, name: &str) -> Vec<Value> {
+    match field(row, name) {
+        Value::List { vals, .. } => vals,
+        _ => vec![],
+    }
+}
+
+/// Reads the text items of a list field.
+pub(crate) fn strings(row: &Value, name: &str) -> Vec<String> {
+    list(row, name)
+        .iter()
+        .filter_map(|value| value.as_str().ok().map(str::to_string))
+        .collect()
+}
+
+/// Collects every result set of a query with its columns, keeping result sets
+/// that have no rows.
+pub async fn collect_results(
+    mut stream: QueryStream<'_>,
+) -> tiberius::Result<Vec<(Vec<Column>, Vec<Row>)>> {
+    let mut results: Vec<(Vec<Column>, Vec<Row>)> = vec![];
+
+    while let Some(item) = stream.next().await {
+        match item? {
+            QueryItem::Metadata(metadata) => results.push((metadata.columns().to_vec(), vec![])),
+            QueryItem::Row(row) => match results.last_mut() {
+                Some((_, rows)) => rows.push(row),
+                None => results.push((row.columns().to_vec(), vec![row])),
+            },
+        }
+    }
+
+    Ok(results)
+}
+
+/// Converts a row into a record with one field per column.
+#[allow(clippy::result_large_err)]
+pub fn parse_row(row: &Row) -> anyhow::Result<Value, LabeledError> {
+    let mut record = Record::new();
+
+    for (col, cell) in row.cells() {
+        record.insert(col.name(), parse_value(cell)?);
+    }
+
+    Ok(Value::record(record, Span::unknown()))
+}
+
 pub fn parse_value(data: &ColumnData<'static>) -> anyhow::Result<Value, LabeledError> {
     match data {
         ColumnData::Binary(Some(val)) => Ok(Value::binary(val.as_ref(), Span::unknown())),
@@ -52,6 +138,12 @@ pub fn parse_value(data: &ColumnData<'static>) -> anyhow::Result<Value, LabeledE
 }
 
 fn parse_time(time: &Time) -> anyhow::Result<Value, LabeledError> {
+    Ok(Value::duration(time_nanoseconds(time)?, Span::unknown()))
+}
+
+/// The number of nanoseconds since midnight of a TIME value.
+#[allow(clippy::result_large_err)]
+pub fn time_nanoseconds(time: &Time) -> anyhow::Result<i64, LabeledError> {
     // Number of 10^-n second increments since midnight, where n is defined in scale.
     let increments = time.increments();
 
@@ -59,9 +151,34 @@ fn parse_time(time: &Time) -> anyhow::Result<Value, LabeledError> {
     let scale = time.scale() as u32;
 
     // Calculate duration in nanoseconds, we use 9 because there are 10^9 nanoseconds in a second
-    let duration = increments * 10u64.pow(9 - scale);
+    9u32.checked_sub(scale)
+        .and_then(|exponent| increments.checked_mul(10u64.pow(exponent)))
+        .and_then(|duration| i64::try_from(duration).ok())
+        .ok_or_else(|| {
+            LabeledError::new("Failed to parse time")
+                .with_label(format!("Invalid time of scale {scale}"), Span::unknown())
+        })
+}
 
-    Ok(Value::duration(duration as i64, Span::unknown()))
+/// Formats a TIME value as `HH:MM:SS` with as many fractional digits as its scale.
+pub fn format_time(time: &Time) -> String {
+    let scale = time.scale() as u32;
+    let per_second = 10u64.pow(scale.min(9));
+    let seconds = time.increments() / per_second;
+    let text = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match scale {
+        0 => text,
+        _ => format!(
+            "{text}.{:0width$}",
+            time.increments() % per_second,
+            width = scale as usize
+        ),
+    }
 }
 
 fn parse_datetime(data: &ColumnData<'static>) -> anyhow::Result<Value, LabeledError> {
@@ -104,3 +221,14 @@ impl Iterator for TableIterator {
         }
     }
 }
+
+#[test]
+fn test_time() {
+    let time = Time::new(37_800 * 10_000_000 + 1_234_567, 7);
+    assert_eq!(format_time(&time), "10:30:00.1234567");
+    assert_eq!(
+        time_nanoseconds(&time).unwrap(),
+        37_800_000_000_000 + 123_456_700
+    );
+    assert_eq!(format_time(&Time::new(86_399, 0)), "23:59:59");
+}
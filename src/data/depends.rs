@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use async_std::net::TcpStream;
+use nu_protocol::{record, LabeledError, Span, Value};
+use tiberius::{Client, ToSql};
+
+use super::{field, int, query_records, text};
+
+pub const DEFAULT_DEPENDS_DEPTH: usize = 10;
+
+/// An object reached while walking dependencies.
+#[derive(Debug, Clone)]
+struct Dependency {
+    id: i32,
+    name: String,
+    kind: Value,
+    depth: usize,
+    /// The object this one was reached from.
+    via: String,
+    /// The columns involved, where SQL Server tracks them.
+    columns: Vec<String>,
+    /// Why the columns could not be read.
+    columns_error: Option<String>,
+}
+
+/// Finds what `object` depends on (upstream) and what depends on it
+/// (downstream), following each direction up to `max_depth` levels.
+///
+/// Upstream references come from `sys.sql_expression_dependencies` and
+/// downstream ones from `sys.dm_sql_referencing_entities`. Referenced columns
+/// are read from `sys.dm_sql_referenced_entities`, in one query per object
+/// walked. It fails for objects that no longer compile, which is reported in
+/// `columns_error` instead. With `column`, only the objects using that column
+/// of `object` are followed, along with those whose columns are unknown.
+pub async fn dependencies(
+    client: &mut Client<TcpStream>,
+    object: &str,
+    column: Option<&str>,
+    max_depth: usize,
+) -> Result<Vec<Value>, LabeledError> {
+    let root = query_records(
+        client,
+        "SELECT o.object_id AS id, s.name + '.' + o.name AS name, COLUMNPROPERTY(o.object_id, @P2, 'ColumnId') AS column_id
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.object_id = OBJECT_ID(@P1)",
+        &[&object, &column],
+    )
+    .await?
+    .pop()
+    .ok_or_else(|| LabeledError::new(format!("Object {object} does not exist")))?;
+
+    let root_id = int(&root, "id") as i32;
+    let root_name = text(&root, "name");
+    let column_id = match (column, root.get_data_by_key("column_id")) {
+        (Some(column), Some(Value::Int { val, .. })) => Some((column, val as i32)),
+        (Some(column), _) => {
+            return Err(LabeledError::new(format!(
+                "Column {column} does not exist in {root_name}"
+            )))
+        }
+        (None, _) => None,
+    };
+
+    let upstream = walk(
+        client,
+        Direction::Upstream,
+        root_id,
+        &root_name,
+        column_id,
+        max_depth,
+    )
+    .await?;
+    let downstream = walk(
+        client,
+        Direction::Downstream,
+        root_id,
+        &root_name,
+        column_id,
+        max_depth,
+    )
+    .await?;
+
+    let span = Span::unknown();
+    let rows = upstream
+        .into_iter()
+        .map(|dependency| ("upstream", dependency))
+        .chain(
+            downstream
+                .into_iter()
+                .map(|dependency| ("downstream", dependency)),
+        )
+        .map(|(direction, dependency)| {
+            Value::record(
+                record! {
+                    "direction" => Value::string(direction, span),
+                    "depth" => Value::int(dependency.depth as i64, span),
+                    "name" => Value::string(dependency.name, span),
+                    "type" => dependency.kind,
+                    "columns" => Value::list(
+                        dependency
+                            .columns
+                            .into_iter()
+                            .map(|column| Value::string(column, span))
+                            .collect(),
+                        span,
+                    ),
+                    "columns_error" => match dependency.columns_error {
+                        Some(error) => Value::string(error, span),
+                        None => Value::nothing(span),
+                    },
+                    "via" => Value::string(dependency.via, span),
+                },
+                span,
+            )
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Upstream,
+    Downstream,
+}
+
+/// Walks the dependency graph breadth first so each object is reported once,
+/// at the shallowest depth it is reached. The column filter only applies to
+/// the references of the root object.
+async fn walk(
+    client: &mut Client<TcpStream>,
+    direction: Direction,
+    root_id: i32,
+    root_name: &str,
+    column: Option<(&str, i32)>,
+    max_depth: usize,
+) -> Result<Vec<Dependency>, LabeledError> {
+    let mut visited = HashSet::from([root_id]);
+    let mut queue = VecDeque::from([(root_id, root_name.to_string(), 1)]);
+    let mut found = vec![];
+
+    while let Some((id, name, depth)) = queue.pop_front() {
+        if depth > max_depth {
+            continue;
+        }
+        let column = column.filter(|_| depth == 1);
+        let dependencies = match direction {
+            Direction::Upstream => upstream(client, id, &name, column.map(|(_, id)| id)).await?,
+            Direction::Downstream => {
+                downstream(client, id, &name, column.map(|(name, _)| name)).await?
+            }
+        };
+
+        for mut dependency in dependencies {
+            // Objects in other databases have no local id and are not followed.
+            if dependency.id != 0 && !visited.insert(dependency.id) {
+                continue;
+            }
+            dependency.depth = depth;
+            if dependency.id != 0 {
+                queue.push_back((dependency.id, dependency.name.clone(), depth + 1));
+            }
+            found.push(dependency);
+        }
+    }
+
+    Ok(found)
+}
+
+async fn upstream(
+    client: &mut Client<TcpStream>,
+    id: i32,
+    name: &str,
+    column_id: Option<i32>,
+) -> Result<Vec<Dependency>, LabeledError> {
+    let rows = query_records(
+        client,
+        "SELECT DISTINCT COALESCE(d.referenced_id, 0) AS id,
+            CONCAT(d.referenced_database_name + '.',
+                COALESCE(OBJECT_SCHEMA_NAME(d.referenced_id), d.referenced_schema_name, 'dbo'),
+                '.', d.referenced_entity_name) AS name,
+            LOWER(o.type_desc) AS type
+        FROM sys.sql_expression_dependencies d
+        LEFT JOIN sys.objects o ON o.object_id = d.referenced_id
+        WHERE d.referencing_id = @P1 AND (@P2 IS NULL OR d.referencing_minor_id = @P2)
+        ORDER BY name",
+        &[&id, &column_id],
+    )
+    .await?;
+    if rows.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let columns = referenced_columns(
+        client,
+        "SELECT DISTINCT referenced_id AS id, referenced_minor_name AS name
+        FROM sys.dm_sql_referenced_entities(
+            QUOTENAME(OBJECT_SCHEMA_NAME(@P1)) + '.' + QUOTENAME(OBJECT_NAME(@P1)), 'OBJECT')
+        WHERE referenced_minor_name IS NOT NULL
+        ORDER BY name",
+        &[&id],
+    )
+    .await;
+
+    let dependencies = rows
+        .iter()
+        .map(|row| {
+            let referenced_id = int(row, "id") as i32;
+            let (columns, columns_error) = columns_of(&columns, referenced_id);
+            Dependency {
+                id: referenced_id,
+                name: text(row, "name"),
+                kind: field(row, "type"),
+                depth: 0,
+                via: name.to_string(),
+                columns,
+                columns_error,
+            }
+        })
+        .collect();
+    Ok(dependencies)
+}
+
+async fn downstream(
+    client: &mut Client<TcpStream>,
+    id: i32,
+    name: &str,
+    column: Option<&str>,
+) -> Result<Vec<Dependency>, LabeledError> {
+    let rows = query_records(
+        client,
+        "SELECT r.referencing_id AS id,
+            r.referencing_schema_name + '.' + r.referencing_entity_name AS name,
+            LOWER(o.type_desc) AS type
+        FROM sys.dm_sql_referencing_entities(
+            QUOTENAME(OBJECT_SCHEMA_NAME(@P1)) + '.' + QUOTENAME(OBJECT_NAME(@P1)), 'OBJECT') r
+        LEFT JOIN sys.objects o ON o.object_id = r.referencing_id
+        ORDER BY name",
+        &[&id],
+    )
+    .await?;
+    if rows.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let columns = referenced_columns(
+        client,
+        "SELECT DISTINCT r.referencing_id AS id, e.referenced_minor_name AS name
+        FROM sys.dm_sql_referencing_entities(
+            QUOTENAME(OBJECT_SCHEMA_NAME(@P1)) + '.' + QUOTENAME(OBJECT_NAME(@P1)), 'OBJECT') r
+        CROSS APPLY sys.dm_sql_referenced_entities(
+            QUOTENAME(r.referencing_schema_name) + '.' + QUOTENAME(r.referencing_entity_name),
+            'OBJECT') e
+        WHERE e.referenced_id = @P1 AND e.referenced_minor_name IS NOT NULL
+        ORDER BY name",
+        &[&id],
+    )
+    .await;
+
+    let mut dependencies = vec![];
+    for row in rows {
+        let referencing_id = int(&row, "id") as i32;
+        let (columns, columns_error) = columns_of(&columns, referencing_id);
+        let unused = column.is_some_and(|column| {
+            columns_error.is_none() && !columns.iter().any(|c| c.eq_ignore_ascii_case(column))
+        });
+        if unused {
+            continue;
+        }
+        dependencies.push(Dependency {
+            id: referencing_id,
+            name: text(&row, "name"),
+            kind: field(&row, "type"),
+            depth: 0,
+            via: name.to_string(),
+            columns,
+            columns_error,
+        });
+    }
+    Ok(dependencies)
+}
+
+/// Reads `id` and column `name` pairs, grouping the columns by object. Fails
+/// with SQL Server's message when it cannot resolve the references of one of
+/// the objects involved.
+async fn referenced_columns(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+    params: &[&dyn ToSql],
+) -> Result<HashMap<i32, Vec<String>>, String> {
+    let rows = query_records(client, sql, params)
+        .await
+        .map_err(|e| e.msg)?;
+
+    let mut columns: HashMap<i32, Vec<String>> = HashMap::new();
+    for row in rows {
+        columns
+            .entry(int(&row, "id") as i32)
+            .or_default()
+            .push(text(&row, "name"));
+    }
+    Ok(columns)
+}
+
+fn columns_of(
+    columns: &Result<HashMap<i32, Vec<String>>, String>,
+    id: i32,
+) -> (Vec<String>, Option<String>) {
+    match columns {
+        Ok(columns) => (columns.get(&id).cloned().unwrap_or_default(), None),
+        Err(error) => (vec![], Some(error.clone())),
+    }
+}
@@ -0,0 +1,312 @@
+use async_std::net::TcpStream;
+use nu_protocol::{record, LabeledError, Record, Span, Value};
+use tiberius::Client;
+
+use super::{columns, field, query_records};
+
+/// Describes `table` with its columns, keys, constraints, indexes, triggers and
+/// extended properties, all read from the catalog views.
+pub async fn describe(client: &mut Client<TcpStream>, table: &str) -> Result<Value, LabeledError> {
+    let span = Span::unknown();
+
+    let object = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS name, LOWER(o.type_desc) AS type
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.object_id = OBJECT_ID(@P1) AND o.type IN ('U', 'V')",
+        &[&table],
+    )
+    .await?
+    .pop()
+    .ok_or_else(|| LabeledError::new(format!("Table {table} does not exist")))?;
+
+    let columns = columns(client, table, None).await?;
+
+    let indexes = group_by_name(
+        query_records(
+            client,
+            "SELECT i.name, LOWER(i.type_desc) AS type, i.is_unique AS [unique],
+                i.is_primary_key AS primary_key, i.is_unique_constraint AS unique_constraint,
+                i.filter_definition AS filter,
+                CASE WHEN ic.is_included_column = 0 THEN c.name END AS columns,
+                CASE WHEN ic.is_included_column = 0 THEN ic.is_descending_key END AS descending,
+                CASE WHEN ic.is_included_column = 1 THEN c.name END AS included
+            FROM sys.indexes i
+            JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+            JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id
+            WHERE i.object_id = OBJECT_ID(@P1) AND i.type > 0
+            ORDER BY i.index_id, ic.is_included_column, ic.key_ordinal, ic.index_column_id",
+            &[&table],
+        )
+        .await?,
+        &["columns", "descending", "included"],
+    );
+
+    let primary_key = indexes
+        .iter()
+        .find(|index| field(index, "primary_key").as_bool().unwrap_or_default())
+        .map(|index| {
+            Value::record(
+                record! {
+                    "name" => field(index, "name"),
+                    "clustered" => Value::bool(
+                        field(index, "type").as_str().is_ok_and(|kind| kind == "clustered"),
+                        span,
+                    ),
+                    "columns" => field(index, "columns"),
+                },
+                span,
+            )
+        })
+        .unwrap_or(Value::nothing(span));
+
+    let unique_constraints = indexes
+        .iter()
+        .filter(|index| {
+            field(index, "unique_constraint")
+                .as_bool()
+                .unwrap_or_default()
+        })
+        .map(|index| {
+            Value::record(
+                record! {
+                    "name" => field(index, "name"),
+                    "columns" => field(index, "columns"),
+                },
+                span,
+            )
+        })
+        .collect();
+
+    let foreign_keys = group_by_name(
+        query_records(
+            client,
+            "SELECT fk.name,
+                c.name AS columns,
+                OBJECT_SCHEMA_NAME(fk.referenced_object_id) + '.'
+                    + OBJECT_NAME(fk.referenced_object_id) AS [references],
+                rc.name AS referenced_columns,
+                LOWER(fk.delete_referential_action_desc) AS on_delete,
+                LOWER(fk.update_referential_action_desc) AS on_update,
+                fk.is_disabled AS disabled, fk.is_not_trusted AS not_trusted
+            FROM sys.foreign_keys fk
+            JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+            JOIN sys.columns c
+                ON c.object_id = fkc.parent_object_id AND c.column_id = fkc.parent_column_id
+            JOIN sys.columns rc
+                ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id
+            WHERE fk.parent_object_id = OBJECT_ID(@P1)
+            ORDER BY fk.name, fkc.constraint_column_id",
+            &[&table],
+        )
+        .await?,
+        &["columns", "referenced_columns"],
+    );
+
+    let referenced_by = group_by(
+        query_records(
+            client,
+            "SELECT fk.name,
+                OBJECT_SCHEMA_NAME(fk.parent_object_id) + '.'
+                    + OBJECT_NAME(fk.parent_object_id) AS [table],
+                c.name AS columns,
+                rc.name AS referenced_columns,
+                LOWER(fk.delete_referential_action_desc) AS on_delete,
+                LOWER(fk.update_referential_action_desc) AS on_update,
+                fk.is_disabled AS disabled
+            FROM sys.foreign_keys fk
+            JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+            JOIN sys.columns c
+                ON c.object_id = fkc.parent_object_id AND c.column_id = fkc.parent_column_id
+            JOIN sys.columns rc
+                ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id
+            WHERE fk.referenced_object_id = OBJECT_ID(@P1)
+            ORDER BY 2, fk.name, fkc.constraint_column_id",
+            &[&table],
+        )
+        .await?,
+        &["table", "name"],
+        &["columns", "referenced_columns"],
+    );
+
+    let check_constraints = query_records(
+        client,
+        "SELECT cc.name, c.name AS [column], cc.definition, cc.is_disabled AS disabled,
+            cc.is_not_trusted AS not_trusted
+        FROM sys.check_constraints cc
+        LEFT JOIN sys.columns c
+            ON c.object_id = cc.parent_object_id AND c.column_id = cc.parent_column_id
+        WHERE cc.parent_object_id = OBJECT_ID(@P1)
+        ORDER BY cc.name",
+        &[&table],
+    )
+    .await?;
+
+    let triggers = group_by_name(
+        query_records(
+            client,
+            "SELECT t.name, t.is_disabled AS disabled, t.is_instead_of_trigger AS instead_of,
+                LOWER(te.type_desc) AS events
+            FROM sys.triggers t
+            JOIN sys.trigger_events te ON te.object_id = t.object_id
+            WHERE t.parent_id = OBJECT_ID(@P1)
+            ORDER BY t.name, te.type",
+            &[&table],
+        )
+        .await?,
+        &["events"],
+    );
+
+    // Properties with a minor id belong to a column rather than the table itself.
+    let extended_properties = query_records(
+        client,
+        "SELECT c.name AS [column], ep.name, CAST(ep.value AS NVARCHAR(MAX)) AS value
+        FROM sys.extended_properties ep
+        LEFT JOIN sys.columns c ON c.object_id = ep.major_id AND c.column_id = ep.minor_id
+        WHERE ep.class = 1 AND ep.major_id = OBJECT_ID(@P1)
+        ORDER BY ep.minor_id, ep.name",
+        &[&table],
+    )
+    .await?;
+
+    let indexes = indexes
+        .into_iter()
+        .map(|mut index| {
+            if let Value::Record { val, .. } = &mut index {
+                val.to_mut().remove("unique_constraint");
+            }
+            index
+        })
+        .collect();
+
+    Ok(Value::record(
+        record! {
+            "name" => field(&object, "name"),
+            "type" => field(&object, "type"),
+            "columns" => Value::list(columns, span),
+            "primary_key" => primary_key,
+            "foreign_keys" => Value::list(foreign_keys, span),
+            "referenced_by" => Value::list(referenced_by, span),
+            "unique_constraints" => Value::list(unique_constraints, span),
+            "check_constraints" => Value::list(check_constraints, span),
+            "indexes" => Value::list(indexes, span),
+            "triggers" => Value::list(triggers, span),
+            "extended_properties" => Value::list(extended_properties, span),
+        },
+        span,
+    ))
+}
+
+/// Merges consecutive records that share a `name`, collecting the non-null
+/// values of `list_fields` into lists. The rows must be ordered by name.
+pub(crate) fn group_by_name(rows: Vec<Value>, list_fields: &[&str]) -> Vec<Value> {
+    group_by(rows, &["name"], list_fields)
+}
+
+/// Merges consecutive records that share the values of `keys`, like
+/// [`group_by_name`] for names that are only unique together with another
+/// field, e.g. foreign keys across tables.
+pub(crate) fn group_by(rows: Vec<Value>, keys: &[&str], list_fields: &[&str]) -> Vec<Value> {
+    let span = Span::unknown();
+    let mut groups: Vec<Record> = vec![];
+
+    for row in rows {
+        let Value::Record { val, .. } = row else {
+            continue;
+        };
+        let record = val.into_owned();
+
+        let same_group = groups
+            .last()
+            .is_some_and(|group| keys.iter().all(|key| group.get(*key) == record.get(*key)));
+        if !same_group {
+            let mut group = Record::new();
+            for (name, value) in record.iter() {
+                match list_fields.contains(&name.as_str()) {
+                    true => group.push(name, Value::list(vec![], span)),
+                    false => group.push(name, value.clone()),
+                }
+            }
+            groups.push(group);
+        }
+
+        let Some(group) = groups.last_mut() else {
+            continue;
+        };
+        for name in list_fields {
+            let value = match record.get(*name) {
+                Some(Value::Nothing { .. }) | None => continue,
+                Some(value) => value.clone(),
+            };
+            if let Some(Value::List { vals, .. }) = group.get_mut(*name) {
+                vals.push(value);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|group| Value::record(group, span))
+        .collect()
+}
+
+#[test]
+fn test_group_by_name() {
+    let span = Span::unknown();
+    let row = |name: &str, column: Option<&str>| {
+        Value::record(
+            record! {
+                "name" => Value::string(name, span),
+                "columns" => column
+                    .map(|column| Value::string(column, span))
+                    .unwrap_or(Value::nothing(span)),
+            },
+            span,
+        )
+    };
+
+    let groups = group_by_name(
+        vec![
+            row("PK_a", Some("id")),
+            row("IX_b", Some("x")),
+            row("IX_b", None),
+            row("IX_b", Some("y")),
+        ],
+        &["columns"],
+    );
+
+    assert_eq!(groups.len(), 2);
+    let columns = field(&groups[1], "columns");
+    let columns: Vec<&str> = columns
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str().unwrap())
+        .collect();
+    assert_eq!(columns, vec!["x", "y"]);
+}
+
+#[test]
+fn test_group_by() {
+    let span = Span::unknown();
+    let row = |table: &str, column: &str| {
+        Value::record(
+            record! {
+                "name" => Value::string("FK_owner", span),
+                "table" => Value::string(table, span),
+                "columns" => Value::string(column, span),
+            },
+            span,
+        )
+    };
+
+    let groups = group_by(
+        vec![row("dbo.a", "x"), row("sales.a", "y"), row("sales.a", "z")],
+        &["table", "name"],
+        &["columns"],
+    );
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(field(&groups[1], "columns").as_list().unwrap().len(), 2);
+}
@@ -0,0 +1,389 @@
+use async_std::net::TcpStream;
+use nu_protocol::LabeledError;
+use tiberius::Client;
+
+use super::{flag, group_by, int, query_records, strings, text, type_declaration};
+
+/// A table drawn as an entity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErTable {
+    pub name: String,
+    pub columns: Vec<ErColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErColumn {
+    pub name: String,
+    pub sql_type: String,
+    pub primary_key: bool,
+    pub foreign_key: bool,
+    pub unique: bool,
+}
+
+/// A foreign key drawn as a relationship from the referencing table to the referenced one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErRelationship {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub references: String,
+    pub referenced_columns: Vec<String>,
+    /// Whether a referencing column is nullable, so a row may have no parent.
+    pub optional: bool,
+    /// Whether the referencing columns are unique, so a parent has at most one child.
+    pub one_to_one: bool,
+}
+
+/// The output formats of [`ErModel::render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErFormat {
+    Mermaid,
+    Dot,
+}
+
+impl ErFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "mermaid" => Some(ErFormat::Mermaid),
+            "dot" | "graphviz" => Some(ErFormat::Dot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErModel {
+    pub tables: Vec<ErTable>,
+    pub relationships: Vec<ErRelationship>,
+}
+
+impl ErModel {
+    /// Reads the tables and foreign keys of the database, or of one schema.
+    pub async fn read(
+        client: &mut Client<TcpStream>,
+        schema: Option<&str>,
+    ) -> Result<Self, LabeledError> {
+        let columns = query_records(
+            client,
+            "SELECT s.name + '.' + t.name AS [table], c.name,
+                CASE WHEN ty.is_user_defined = 1 AND ty.is_assembly_type = 0
+                    THEN TYPE_NAME(c.system_type_id) ELSE ty.name END AS type_name,
+                c.max_length, c.precision, c.scale,
+                CAST(CASE WHEN EXISTS (
+                    SELECT 1 FROM sys.indexes i
+                    JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+                    WHERE i.object_id = t.object_id AND i.is_primary_key = 1
+                        AND ic.column_id = c.column_id
+                ) THEN 1 ELSE 0 END AS BIT) AS primary_key,
+                CAST(CASE WHEN EXISTS (
+                    SELECT 1 FROM sys.foreign_key_columns f
+                    WHERE f.parent_object_id = t.object_id AND f.parent_column_id = c.column_id
+                ) THEN 1 ELSE 0 END AS BIT) AS foreign_key,
+                CAST(CASE WHEN EXISTS (
+                    SELECT 1 FROM sys.indexes i
+                    JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+                    WHERE i.object_id = t.object_id AND i.is_unique = 1 AND i.is_primary_key = 0
+                        AND ic.is_included_column = 0 AND ic.column_id = c.column_id
+                ) THEN 1 ELSE 0 END AS BIT) AS [unique]
+            FROM sys.tables t
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            JOIN sys.columns c ON c.object_id = t.object_id
+            JOIN sys.types ty ON ty.user_type_id = c.user_type_id
+            WHERE t.is_ms_shipped = 0 AND (@P1 IS NULL OR s.name = @P1)
+            ORDER BY s.name, t.name, c.column_id",
+            &[&schema],
+        )
+        .await?;
+
+        let mut tables: Vec<ErTable> = vec![];
+        for column in columns {
+            let table = text(&column, "table");
+            let column = ErColumn {
+                name: text(&column, "name"),
+                sql_type: type_declaration(
+                    &text(&column, "type_name"),
+                    int(&column, "max_length") as i16,
+                    int(&column, "precision") as u8,
+                    int(&column, "scale") as u8,
+                ),
+                primary_key: flag(&column, "primary_key"),
+                foreign_key: flag(&column, "foreign_key"),
+                unique: flag(&column, "unique"),
+            };
+            match tables.last_mut() {
+                Some(last) if last.name == table => last.columns.push(column),
+                _ => tables.push(ErTable {
+                    name: table,
+                    columns: vec![column],
+                }),
+            }
+        }
+
+        // A relationship is one-to-one when a unique index has exactly the
+        // referencing columns as its key.
+        let relationships = group_by(
+            query_records(
+                client,
+                "SELECT fk.name, s.name + '.' + t.name AS [table], c.name AS columns,
+                    OBJECT_SCHEMA_NAME(fk.referenced_object_id) + '.'
+                        + OBJECT_NAME(fk.referenced_object_id) AS [references],
+                    rc.name AS referenced_columns,
+                    CAST(CASE WHEN EXISTS (
+                        SELECT 1 FROM sys.foreign_key_columns f
+                        JOIN sys.columns fc
+                            ON fc.object_id = f.parent_object_id AND fc.column_id = f.parent_column_id
+                        WHERE f.constraint_object_id = fk.object_id AND fc.is_nullable = 1
+                    ) THEN 1 ELSE 0 END AS BIT) AS optional,
+                    CAST(CASE WHEN EXISTS (
+                        SELECT 1 FROM sys.indexes i
+                        WHERE i.object_id = fk.parent_object_id AND i.is_unique = 1
+                            AND NOT EXISTS (
+                                SELECT ic.column_id FROM sys.index_columns ic
+                                WHERE ic.object_id = i.object_id AND ic.index_id = i.index_id
+                                    AND ic.is_included_column = 0
+                                EXCEPT
+                                SELECT f.parent_column_id FROM sys.foreign_key_columns f
+                                WHERE f.constraint_object_id = fk.object_id
+                            )
+                            AND NOT EXISTS (
+                                SELECT f.parent_column_id FROM sys.foreign_key_columns f
+                                WHERE f.constraint_object_id = fk.object_id
+                                EXCEPT
+                                SELECT ic.column_id FROM sys.index_columns ic
+                                WHERE ic.object_id = i.object_id AND ic.index_id = i.index_id
+                                    AND ic.is_included_column = 0
+                            )
+                    ) THEN 1 ELSE 0 END AS BIT) AS one_to_one
+                FROM sys.foreign_keys fk
+                JOIN sys.tables t ON t.object_id = fk.parent_object_id
+                JOIN sys.schemas s ON s.schema_id = t.schema_id
+                JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+                JOIN sys.columns c
+                    ON c.object_id = fkc.parent_object_id AND c.column_id = fkc.parent_column_id
+                JOIN sys.columns rc
+                    ON rc.object_id = fkc.referenced_object_id
+                    AND rc.column_id = fkc.referenced_column_id
+                WHERE t.is_ms_shipped = 0 AND (@P1 IS NULL OR s.name = @P1)
+                ORDER BY s.name, t.name, fk.name, fkc.constraint_column_id",
+                &[&schema],
+            )
+            .await?,
+            &["table", "name"],
+            &["columns", "referenced_columns"],
+        )
+        .iter()
+        .map(|row| ErRelationship {
+            name: text(row, "name"),
+            table: text(row, "table"),
+            columns: strings(row, "columns"),
+            references: text(row, "references"),
+            referenced_columns: strings(row, "referenced_columns"),
+            optional: flag(row, "optional"),
+            one_to_one: flag(row, "one_to_one"),
+        })
+        .collect();
+
+        Ok(ErModel {
+            tables,
+            relationships,
+        })
+    }
+
+    pub fn render(&self, format: ErFormat) -> String {
+        match format {
+            ErFormat::Mermaid => self.mermaid(),
+            ErFormat::Dot => self.dot(),
+        }
+    }
+
+    /// Renders a Mermaid `erDiagram`. Entity names cannot contain dots, so
+    /// `dbo.Users` is written as `dbo_Users`.
+    pub fn mermaid(&self) -> String {
+        let mut text = String::from("erDiagram\n");
+
+        for table in &self.tables {
+            text.push_str(&format!("    {} {{\n", mermaid_name(&table.name)));
+            for column in &table.columns {
+                // Attribute types cannot contain commas, as in `decimal(10, 2)`.
+                let sql_type = match column.sql_type.contains(',') {
+                    true => column.sql_type.split('(').next().unwrap_or_default(),
+                    false => column.sql_type.as_str(),
+                };
+                let keys = [
+                    (column.primary_key, "PK"),
+                    (column.foreign_key, "FK"),
+                    (column.unique, "UK"),
+                ]
+                .iter()
+                .filter(|(is_key, _)| *is_key)
+                .map(|(_, key)| *key)
+                .collect::<Vec<_>>()
+                .join(", ");
+                text.push_str(&format!(
+                    "        {sql_type} {}{}{keys}\n",
+                    mermaid_name(&column.name),
+                    if keys.is_empty() { "" } else { " " }
+                ));
+            }
+            text.push_str("    }\n");
+        }
+
+        for relationship in &self.relationships {
+            text.push_str(&format!(
+                "    {} {}--{} {} : \"{}\"\n",
+                mermaid_name(&relationship.references),
+                match relationship.optional {
+                    true => "|o",
+                    false => "||",
+                },
+                match relationship.one_to_one {
+                    true => "o|",
+                    false => "o{",
+                },
+                mermaid_name(&relationship.table),
+                relationship.name.replace('"', "'")
+            ));
+        }
+
+        text
+    }
+
+    /// Renders a Graphviz digraph with one record node per table and an edge
+    /// from each referencing column to the referenced column.
+    pub fn dot(&self) -> String {
+        let mut text = String::from(
+            "digraph er {\n    rankdir=LR;\n    node [shape=record, fontname=\"Helvetica\"];\n",
+        );
+
+        for table in &self.tables {
+            let columns = table
+                .columns
+                .iter()
+                .map(|column| {
+                    let mut label = format!("{} : {}", column.name, column.sql_type);
+                    if column.primary_key {
+                        label.push_str(" PK");
+                    }
+                    if column.foreign_key {
+                        label.push_str(" FK");
+                    }
+                    format!("<{}> {}\\l", dot_port(&column.name), dot_escape(&label))
+                })
+                .collect::<String>();
+            text.push_str(&format!(
+                "    \"{}\" [label=\"{{{}|{columns}}}\"];\n",
+                dot_escape(&table.name),
+                dot_escape(&table.name)
+            ));
+        }
+
+        for relationship in &self.relationships {
+            let port = |columns: &[String]| {
+                columns
+                    .first()
+                    .map(|column| format!(":\"{}\"", dot_port(column)))
+                    .unwrap_or_default()
+            };
+            text.push_str(&format!(
+                "    \"{}\"{} -> \"{}\"{} [label=\"{}\", taillabel=\"{}\", headlabel=\"{}\"];\n",
+                dot_escape(&relationship.table),
+                port(&relationship.columns),
+                dot_escape(&relationship.references),
+                port(&relationship.referenced_columns),
+                dot_escape(&relationship.name),
+                match relationship.one_to_one {
+                    true => "0..1",
+                    false => "*",
+                },
+                match relationship.optional {
+                    true => "0..1",
+                    false => "1",
+                },
+            ));
+        }
+
+        text.push_str("}\n");
+        text
+    }
+}
+
+fn mermaid_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_alphanumeric() || c == '_' || c == '-' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn dot_port(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_alphanumeric() || c == '_' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Escapes the characters with a meaning in record labels and quoted strings.
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[test]
+fn test_mermaid() {
+    let column = |name: &str, sql_type: &str, primary_key: bool, foreign_key: bool| ErColumn {
+        name: name.to_string(),
+        sql_type: sql_type.to_string(),
+        primary_key,
+        foreign_key,
+        unique: false,
+    };
+    let diagram = ErModel {
+        tables: vec![
+            ErTable {
+                name: "dbo.Customers".to_string(),
+                columns: vec![column("id", "int", true, false)],
+            },
+            ErTable {
+                name: "dbo.Orders".to_string(),
+                columns: vec![
+                    column("id", "int", true, false),
+                    column("customer_id", "int", false, true),
+                    column("total", "decimal(10, 2)", false, false),
+                ],
+            },
+        ],
+        relationships: vec![ErRelationship {
+            name: "FK_Orders_Customers".to_string(),
+            table: "dbo.Orders".to_string(),
+            columns: vec!["customer_id".to_string()],
+            references: "dbo.Customers".to_string(),
+            referenced_columns: vec!["id".to_string()],
+            optional: false,
+            one_to_one: false,
+        }],
+    };
+
+    assert_eq!(
+        diagram.mermaid(),
+        "erDiagram
+    dbo_Customers {
+        int id PK
+    }
+    dbo_Orders {
+        int id PK
+        int customer_id FK
+        decimal total
+    }
+    dbo_Customers ||--o{ dbo_Orders : \"FK_Orders_Customers\"
+"
+    );
+}
@@ -0,0 +1,277 @@
+use async_std::net::TcpStream;
+use nu_protocol::{LabeledError, Value};
+use tiberius::{Client, ColumnData, ToSql};
+
+use super::{infer_column_data, parameter_type, run_batch, SqlParam};
+
+pub const DEFAULT_EXEC_BATCH_SIZE: usize = 100;
+
+/// SQL Server rejects requests with more than 2100 parameters.
+const MAX_PARAMETERS: usize = 2100;
+
+const SAVEPOINT: &str = "mssql_exec";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowStatus {
+    Ok,
+    Error(String),
+    RolledBack,
+}
+
+#[derive(Debug, Clone)]
+pub struct RowResult {
+    pub rows_affected: Option<u64>,
+    pub status: RowStatus,
+}
+
+/// The named parameters bound for one input row.
+struct RowParams(Vec<(String, ColumnData<'static>)>);
+
+impl RowParams {
+    #[allow(clippy::result_large_err)]
+    fn from_value(value: &Value) -> Result<Self, LabeledError> {
+        let record = match value {
+            Value::Record { val, .. } => val,
+            Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+            other => {
+                return Err(LabeledError::new(format!(
+                    "Expected a record but got {}",
+                    other.get_type()
+                ))
+                .with_label("not a record", other.span()))
+            }
+        };
+
+        let mut params = vec![];
+        for (name, value) in record.iter() {
+            // Fields that aren't valid parameter names can't be referenced by the statement.
+            if is_parameter_name(name) {
+                params.push((name.clone(), infer_column_data(value)?));
+            }
+        }
+        Ok(Self(params))
+    }
+
+    /// The `sp_executesql` call running the statement (always `@P1`) with this row's values.
+    fn call(&self, first_param: usize) -> String {
+        let definitions = self
+            .0
+            .iter()
+            .map(|(name, data)| format!("@{name} {}", parameter_type(data)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut call = format!("EXEC sp_executesql @P1, N'{definitions}'");
+        for (index, (name, _)) in self.0.iter().enumerate() {
+            call.push_str(&format!(", @{name} = @P{}", first_param + index));
+        }
+        call
+    }
+}
+
+fn is_parameter_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Runs `statement` once for every record in `rows`, binding fields to `@name` parameters.
+///
+/// Rows are sent `batch_size` at a time inside a single transaction. When a batch
+/// fails it is rolled back and retried row by row to find the failing rows. The
+/// transaction is only committed when every row succeeds, otherwise every row
+/// that ran is reported as rolled back. An error that ends the transaction
+/// itself stops the command instead.
+pub async fn execute_each(
+    client: &mut Client<TcpStream>,
+    statement: &str,
+    rows: impl Iterator<Item = Value>,
+    batch_size: usize,
+) -> Result<Vec<RowResult>, LabeledError> {
+    let mut results: Vec<RowResult> = vec![];
+    let mut rows = rows.peekable();
+
+    run_batch(client, "BEGIN TRANSACTION").await?;
+
+    while rows.peek().is_some() {
+        let mut batch: Vec<RowParams> = vec![];
+        let mut param_count = 1;
+        while batch.len() < batch_size {
+            let next_count = match rows.peek() {
+                Some(Value::Record { val, .. }) => val.len(),
+                Some(_) => 0,
+                None => break,
+            };
+            if !batch.is_empty() && param_count + next_count > MAX_PARAMETERS {
+                break;
+            }
+            if let Some(value) = rows.next() {
+                let params = match RowParams::from_value(&value) {
+                    Ok(params) => params,
+                    Err(e) => {
+                        run_batch(client, "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION").await?;
+                        return Err(e);
+                    }
+                };
+                param_count += params.0.len();
+                batch.push(params);
+            }
+        }
+
+        match run_rows(client, statement, &batch).await {
+            Ok(counts) => {
+                check_transaction(client, None).await?;
+                results.extend(counts.into_iter().map(|count| RowResult {
+                    rows_affected: Some(count),
+                    status: RowStatus::Ok,
+                }))
+            }
+            Err(e) if batch.len() > 1 => {
+                check_transaction(client, Some(&e)).await?;
+                for row in batch {
+                    let result = match run_rows(client, statement, std::slice::from_ref(&row)).await
+                    {
+                        Ok(counts) => {
+                            check_transaction(client, None).await?;
+                            RowResult {
+                                rows_affected: counts.first().copied(),
+                                status: RowStatus::Ok,
+                            }
+                        }
+                        Err(e) => {
+                            check_transaction(client, Some(&e)).await?;
+                            RowResult {
+                                rows_affected: None,
+                                status: RowStatus::Error(e),
+                            }
+                        }
+                    };
+                    results.push(result);
+                }
+            }
+            Err(e) => {
+                check_transaction(client, Some(&e)).await?;
+                results.push(RowResult {
+                    rows_affected: None,
+                    status: RowStatus::Error(e),
+                });
+            }
+        }
+    }
+
+    if results.iter().all(|result| result.status == RowStatus::Ok) {
+        run_batch(client, "COMMIT TRANSACTION").await?;
+    } else {
+        run_batch(client, "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION").await?;
+        for result in results.iter_mut() {
+            if result.status == RowStatus::Ok {
+                result.status = RowStatus::RolledBack;
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Runs the statement for each row in one round trip, returning the rows affected by each.
+async fn run_rows(
+    client: &mut Client<TcpStream>,
+    statement: &str,
+    rows: &[RowParams],
+) -> Result<Vec<u64>, String> {
+    let mut sql = format!(
+        "SAVE TRANSACTION {SAVEPOINT};
+        DECLARE @mssql_counts TABLE ([n] INT IDENTITY, [affected] BIGINT);\n"
+    );
+    let mut params = vec![SqlParam(ColumnData::String(Some(
+        statement.to_string().into(),
+    )))];
+
+    for row in rows {
+        sql.push_str(&row.call(params.len() + 1));
+        sql.push_str(";\nINSERT @mssql_counts ([affected]) VALUES (@@ROWCOUNT);\n");
+        params.extend(row.0.iter().map(|(_, data)| SqlParam(data.clone())));
+    }
+    sql.push_str("SELECT [affected] FROM @mssql_counts ORDER BY [n];");
+
+    let params: Vec<&dyn ToSql> = params.iter().map(|p| p as &dyn ToSql).collect();
+    let results = client
+        .query(sql, &params)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_results()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The statement may return its own result sets, the counts are always last.
+    let counts = results
+        .last()
+        .map(|rows| {
+            rows.iter()
+                .map(|row| row.get::<i64, _>(0).unwrap_or_default() as u64)
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(counts)
+}
+
+/// Checks that the transaction is still open and committable after a batch,
+/// rolling back to the savepoint when the batch failed with `error`.
+///
+/// Errors that abort the batch can roll back or doom the whole transaction,
+/// so rows that ran after it would no longer be rolled back with it. The
+/// transaction is then rolled back and the command stops instead.
+async fn check_transaction(
+    client: &mut Client<TcpStream>,
+    error: Option<&str>,
+) -> Result<(), LabeledError> {
+    let rollback = match error {
+        Some(_) => format!("IF XACT_STATE() = 1 ROLLBACK TRANSACTION {SAVEPOINT};\n"),
+        None => String::new(),
+    };
+    let check_error = |e| LabeledError::new(format!("Error checking the transaction: {e}"));
+    let row = client
+        .simple_query(format!("{rollback}SELECT @@TRANCOUNT, XACT_STATE();"))
+        .await
+        .map_err(check_error)?
+        .into_row()
+        .await
+        .map_err(check_error)?;
+    let (transactions, state) = row.map_or((0, 0), |row| {
+        (
+            row.get::<i32, _>(0).unwrap_or_default(),
+            row.get::<i16, _>(1).unwrap_or_default(),
+        )
+    });
+
+    if transactions > 0 && state == 1 {
+        return Ok(());
+    }
+    let _ = run_batch(client, "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION").await;
+    Err(LabeledError::new(match error {
+        Some(error) => {
+            format!("The transaction was ended by an error, every row was rolled back: {error}")
+        }
+        None => "The statement ended the transaction, earlier rows may have been committed".into(),
+    }))
+}
+
+#[test]
+fn test_row_params_call() {
+    use nu_protocol::{record, Span};
+
+    let span = Span::unknown();
+    let row = Value::record(
+        record! {
+            "Email" => Value::string("ash@pokemon.com", span),
+            "UserID" => Value::int(1, span),
+            "not a param" => Value::int(2, span),
+        },
+        span,
+    );
+
+    let params = RowParams::from_value(&row).unwrap();
+    assert_eq!(
+        params.call(2),
+        "EXEC sp_executesql @P1, N'@Email nvarchar(max), @UserID bigint', @Email = @P2, @UserID = @P3"
+    );
+}
@@ -0,0 +1,633 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use flate2::write::GzEncoder;
+use nu_protocol::{record, LabeledError, Record, Span, Value};
+use parquet::{
+    basic::{
+        Compression as ParquetCompression, GzipLevel, LogicalType, Repetition, TimeUnit,
+        Type as PhysicalType, ZstdLevel,
+    },
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    format::MicroSeconds,
+    schema::types::Type as ParquetType,
+};
+
+/// The number of rows written to each parquet row group.
+const ROW_GROUP_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    JsonLines,
+    Parquet,
+}
+
+impl ExportFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "jsonl" => Ok(Self::JsonLines),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(LabeledError::new(format!(
+                "Unknown export format {format}, expected csv, tsv, jsonl or parquet"
+            ))),
+        }
+    }
+}
+
+/// How exported files are compressed. Parquet files compress their pages
+/// instead of the whole file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(compression: &str) -> Result<Self, LabeledError> {
+        match compression.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(LabeledError::new(format!(
+                "Unknown compression {compression}, expected none, gzip or zstd"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub output: PathBuf,
+    pub compression: Compression,
+    /// Start a new file after this many rows, numbering the files
+    /// `name_1.csv`, `name_2.csv`, ...
+    pub max_rows: Option<usize>,
+}
+
+/// A file written by [`export`].
+#[derive(Debug, Clone)]
+pub struct ExportedFile {
+    pub path: PathBuf,
+    pub rows: usize,
+    pub bytes: u64,
+}
+
+/// Writes `values` to the files described by `options` as they arrive,
+/// returning the files written. An error value in `values` stops the export.
+#[allow(clippy::result_large_err)]
+pub fn export(
+    values: impl Iterator<Item = Value>,
+    options: &ExportOptions,
+) -> Result<Vec<ExportedFile>, LabeledError> {
+    let mut files: Vec<ExportedFile> = vec![];
+    let mut writer: Option<Box<dyn RowWriter>> = None;
+    let mut rows = 0;
+
+    for value in values {
+        if let Value::Error { error, .. } = value {
+            return Err(LabeledError::from(*error));
+        }
+
+        if options.max_rows.is_some_and(|max_rows| rows == max_rows) {
+            if let Some(writer) = writer.take() {
+                files.push(close_file(
+                    writer,
+                    &options.output,
+                    files.len(),
+                    options,
+                    rows,
+                )?);
+            }
+            rows = 0;
+        }
+
+        let writer = match &mut writer {
+            Some(writer) => writer,
+            None => writer.insert(open_writer(
+                &file_path(&options.output, files.len(), options.max_rows.is_some()),
+                options,
+            )?),
+        };
+        writer.write(value)?;
+        rows += 1;
+    }
+
+    // An empty export still creates its file, so that readers find it.
+    let writer = match writer {
+        Some(writer) => writer,
+        None if files.is_empty() => open_writer(
+            &file_path(&options.output, 0, options.max_rows.is_some()),
+            options,
+        )?,
+        None => return Ok(files),
+    };
+    files.push(close_file(
+        writer,
+        &options.output,
+        files.len(),
+        options,
+        rows,
+    )?);
+    Ok(files)
+}
+
+#[allow(clippy::result_large_err)]
+fn close_file(
+    writer: Box<dyn RowWriter>,
+    output: &Path,
+    index: usize,
+    options: &ExportOptions,
+    rows: usize,
+) -> Result<ExportedFile, LabeledError> {
+    writer.finish()?;
+    let path = file_path(output, index, options.max_rows.is_some());
+    let bytes = std::fs::metadata(&path)
+        .map_err(|e| LabeledError::new(format!("Error reading {}: {e}", path.display())))?
+        .len();
+    Ok(ExportedFile { path, rows, bytes })
+}
+
+/// The path of the `index`th file, numbered from 1 before the first `.` of the
+/// file name when the export is split.
+fn file_path(output: &Path, index: usize, split: bool) -> PathBuf {
+    if !split {
+        return output.to_path_buf();
+    }
+    let name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match name.split_once('.') {
+        Some((stem, extensions)) => format!("{stem}_{}.{extensions}", index + 1),
+        None => format!("{name}_{}", index + 1),
+    };
+    output.with_file_name(name)
+}
+
+impl ExportedFile {
+    pub fn into_value(self, span: Span) -> Value {
+        Value::record(
+            record! {
+                "path" => Value::string(self.path.to_string_lossy(), span),
+                "rows" => Value::int(self.rows as i64, span),
+                "bytes" => Value::filesize(self.bytes as i64, span),
+            },
+            span,
+        )
+    }
+}
+
+trait RowWriter {
+    #[allow(clippy::result_large_err)]
+    fn write(&mut self, value: Value) -> Result<(), LabeledError>;
+    #[allow(clippy::result_large_err)]
+    fn finish(self: Box<Self>) -> Result<(), LabeledError>;
+}
+
+#[allow(clippy::result_large_err)]
+fn open_writer(path: &Path, options: &ExportOptions) -> Result<Box<dyn RowWriter>, LabeledError> {
+    let file = File::create(path)
+        .map_err(|e| LabeledError::new(format!("Error creating {}: {e}", path.display())))?;
+
+    if options.format == ExportFormat::Parquet {
+        return Ok(Box::new(ParquetWriter {
+            file: Some(file),
+            writer: None,
+            columns: vec![],
+            rows: vec![],
+            compression: options.compression,
+        }));
+    }
+
+    let sink = match options.compression {
+        Compression::None => Sink::Plain(BufWriter::new(file)),
+        Compression::Gzip => Sink::Gzip(GzEncoder::new(
+            BufWriter::new(file),
+            flate2::Compression::default(),
+        )),
+        Compression::Zstd => {
+            Sink::Zstd(zstd::Encoder::new(BufWriter::new(file), 0).map_err(|e| write_error(&e))?)
+        }
+    };
+
+    Ok(match options.format {
+        ExportFormat::JsonLines => Box::new(JsonLinesWriter { sink }),
+        format => Box::new(DelimitedWriter {
+            writer: csv::WriterBuilder::new()
+                .delimiter(if format == ExportFormat::Tsv {
+                    b'\t'
+                } else {
+                    b','
+                })
+                .from_writer(sink),
+            columns: None,
+        }),
+    })
+}
+
+fn write_error(e: &dyn std::fmt::Display) -> LabeledError {
+    LabeledError::new(format!("Error writing export: {e}"))
+}
+
+#[allow(clippy::result_large_err)]
+fn expect_record(value: Value) -> Result<Record, LabeledError> {
+    match value {
+        Value::Record { val, .. } => Ok(val.into_owned()),
+        value => Err(LabeledError::new(format!(
+            "Only records can be exported as a table, found {}",
+            value.get_type()
+        ))),
+    }
+}
+
+/// A file, compressed as it is written.
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Sink::Plain(writer) => writer.write(buf),
+            Sink::Gzip(writer) => writer.write(buf),
+            Sink::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            Sink::Gzip(writer) => writer.flush(),
+            Sink::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+impl Sink {
+    fn finish(self) -> std::io::Result<()> {
+        let mut file = match self {
+            Sink::Plain(writer) => writer,
+            Sink::Gzip(writer) => writer.finish()?,
+            Sink::Zstd(writer) => writer.finish()?,
+        };
+        file.flush()
+    }
+}
+
+/// Writes CSV or TSV with a header taken from the first record.
+struct DelimitedWriter {
+    writer: csv::Writer<Sink>,
+    columns: Option<Vec<String>>,
+}
+
+impl RowWriter for DelimitedWriter {
+    fn write(&mut self, value: Value) -> Result<(), LabeledError> {
+        let mut record = expect_record(value)?;
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => {
+                let columns: Vec<String> = record.columns().cloned().collect();
+                self.writer
+                    .write_record(&columns)
+                    .map_err(|e| write_error(&e))?;
+                self.columns.insert(columns)
+            }
+        };
+
+        let fields = columns
+            .iter()
+            .map(|column| record.remove(column).map(text).unwrap_or_default());
+        self.writer
+            .write_record(fields.collect::<Vec<_>>())
+            .map_err(|e| write_error(&e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), LabeledError> {
+        self.writer
+            .into_inner()
+            .map_err(|e| write_error(&e))?
+            .finish()
+            .map_err(|e| write_error(&e))
+    }
+}
+
+/// Writes one JSON value per line.
+struct JsonLinesWriter {
+    sink: Sink,
+}
+
+impl RowWriter for JsonLinesWriter {
+    fn write(&mut self, value: Value) -> Result<(), LabeledError> {
+        serde_json::to_writer(&mut self.sink, &to_json(value)).map_err(|e| write_error(&e))?;
+        self.sink.write_all(b"\n").map_err(|e| write_error(&e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), LabeledError> {
+        self.sink.finish().map_err(|e| write_error(&e))
+    }
+}
+
+/// Converts a value to the text of a CSV field. Nested values are written as JSON.
+fn text(value: Value) -> String {
+    match value {
+        Value::Nothing { .. } => String::new(),
+        Value::String { val, .. } => val,
+        Value::Date { val, .. } => val.to_rfc3339(),
+        Value::Binary { val, .. } => hex::encode(val),
+        Value::Duration { val, .. } => val.to_string(),
+        Value::Filesize { val, .. } => val.to_string(),
+        value @ (Value::Record { .. } | Value::List { .. }) => to_json(value).to_string(),
+        value => value.to_abbreviated_string(&nu_protocol::Config::default()),
+    }
+}
+
+pub(crate) fn to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Nothing { .. } => serde_json::Value::Null,
+        Value::Bool { val, .. } => val.into(),
+        Value::Int { val, .. } => val.into(),
+        Value::Float { val, .. } => val.into(),
+        Value::Filesize { val, .. } => val.into(),
+        Value::Duration { val, .. } => val.into(),
+        Value::String { val, .. } => val.into(),
+        Value::Date { val, .. } => val.to_rfc3339().into(),
+        Value::Binary { val, .. } => hex::encode(val).into(),
+        Value::List { vals, .. } => vals.into_iter().map(to_json).collect(),
+        Value::Record { val, .. } => serde_json::Value::Object(
+            val.into_owned()
+                .into_iter()
+                .map(|(column, value)| (column, to_json(value)))
+                .collect(),
+        ),
+        value => text(value).into(),
+    }
+}
+
+/// The parquet type a column is written as, taken from its first non-null value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParquetColumn {
+    Int,
+    Float,
+    Bool,
+    Timestamp,
+    Binary,
+    Text,
+}
+
+impl ParquetColumn {
+    fn infer<'a>(mut values: impl Iterator<Item = &'a Value>) -> Self {
+        match values.find(|value| !value.is_nothing()) {
+            Some(Value::Int { .. } | Value::Filesize { .. } | Value::Duration { .. }) => Self::Int,
+            Some(Value::Float { .. }) => Self::Float,
+            Some(Value::Bool { .. }) => Self::Bool,
+            Some(Value::Date { .. }) => Self::Timestamp,
+            Some(Value::Binary { .. }) => Self::Binary,
+            _ => Self::Text,
+        }
+    }
+
+    fn schema(self, name: &str) -> ParquetType {
+        let (physical, logical) = match self {
+            Self::Int => (PhysicalType::INT64, None),
+            Self::Float => (PhysicalType::DOUBLE, None),
+            Self::Bool => (PhysicalType::BOOLEAN, None),
+            Self::Timestamp => (
+                PhysicalType::INT64,
+                Some(LogicalType::Timestamp {
+                    is_adjusted_to_u_t_c: true,
+                    unit: TimeUnit::MICROS(MicroSeconds {}),
+                }),
+            ),
+            Self::Binary => (PhysicalType::BYTE_ARRAY, None),
+            Self::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        };
+        ParquetType::primitive_type_builder(name, physical)
+            .with_repetition(Repetition::OPTIONAL)
+            .with_logical_type(logical)
+            .build()
+            .expect("primitive parquet types are valid")
+    }
+}
+
+/// Writes parquet row groups of [`ROW_GROUP_SIZE`] rows, with the schema
+/// inferred from the first row group.
+struct ParquetWriter {
+    file: Option<File>,
+    writer: Option<SerializedFileWriter<File>>,
+    columns: Vec<(String, ParquetColumn)>,
+    rows: Vec<Record>,
+    compression: Compression,
+}
+
+impl ParquetWriter {
+    #[allow(clippy::result_large_err)]
+    fn flush_rows(&mut self) -> Result<(), LabeledError> {
+        let parquet_error = |e: parquet::errors::ParquetError| write_error(&e);
+
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let names: Vec<String> = match self.rows.first() {
+                    Some(row) => row.columns().cloned().collect(),
+                    None => vec![],
+                };
+                self.columns = names
+                    .into_iter()
+                    .map(|name| {
+                        let kind =
+                            ParquetColumn::infer(self.rows.iter().filter_map(|row| row.get(&name)));
+                        (name, kind)
+                    })
+                    .collect();
+
+                let schema = ParquetType::group_type_builder("schema")
+                    .with_fields(
+                        self.columns
+                            .iter()
+                            .map(|(name, kind)| Arc::new(kind.schema(name)))
+                            .collect(),
+                    )
+                    .build()
+                    .map_err(parquet_error)?;
+                let properties = WriterProperties::builder()
+                    .set_compression(match self.compression {
+                        Compression::None => ParquetCompression::UNCOMPRESSED,
+                        Compression::Gzip => ParquetCompression::GZIP(GzipLevel::default()),
+                        Compression::Zstd => ParquetCompression::ZSTD(ZstdLevel::default()),
+                    })
+                    .build();
+                let file = self
+                    .file
+                    .take()
+                    .expect("file is open until the first row group");
+                self.writer.insert(
+                    SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))
+                        .map_err(parquet_error)?,
+                )
+            }
+        };
+
+        let rows = std::mem::take(&mut self.rows);
+        let mut group = writer.next_row_group().map_err(parquet_error)?;
+        for (name, kind) in &self.columns {
+            let mut column = group
+                .next_column()
+                .map_err(parquet_error)?
+                .expect("a column writer for each schema field");
+            let values = rows
+                .iter()
+                .map(|row| row.get(name).filter(|value| !value.is_nothing()));
+            let levels: Vec<i16> = values.clone().map(|value| value.is_some() as i16).collect();
+            let mismatch = |value: &Value| {
+                LabeledError::new(format!(
+                    "Column {name} holds a {} after values written as {kind:?}",
+                    value.get_type()
+                ))
+            };
+
+            match kind {
+                ParquetColumn::Int => {
+                    let values = values
+                        .flatten()
+                        .map(|value| match value {
+                            Value::Int { val, .. } => Ok(*val),
+                            Value::Filesize { val, .. } => Ok(*val),
+                            Value::Duration { val, .. } => Ok(*val),
+                            value => Err(mismatch(value)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, Some(&levels), None)
+                        .map_err(parquet_error)?;
+                }
+                ParquetColumn::Float => {
+                    let values = values
+                        .flatten()
+                        .map(|value| match value {
+                            Value::Float { val, .. } => Ok(*val),
+                            Value::Int { val, .. } => Ok(*val as f64),
+                            value => Err(mismatch(value)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, Some(&levels), None)
+                        .map_err(parquet_error)?;
+                }
+                ParquetColumn::Bool => {
+                    let values = values
+                        .flatten()
+                        .map(|value| value.as_bool().map_err(|_| mismatch(value)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&values, Some(&levels), None)
+                        .map_err(parquet_error)?;
+                }
+                ParquetColumn::Timestamp => {
+                    let values = values
+                        .flatten()
+                        .map(|value| match value {
+                            Value::Date { val, .. } => Ok(val.timestamp_micros()),
+                            value => Err(mismatch(value)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, Some(&levels), None)
+                        .map_err(parquet_error)?;
+                }
+                ParquetColumn::Binary | ParquetColumn::Text => {
+                    let values = values
+                        .flatten()
+                        .map(|value| match value {
+                            Value::Binary { val, .. } => ByteArray::from(val.clone()),
+                            value => ByteArray::from(text(value.clone()).into_bytes()),
+                        })
+                        .collect::<Vec<_>>();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)
+                        .map_err(parquet_error)?;
+                }
+            }
+            column.close().map_err(parquet_error)?;
+        }
+        group.close().map_err(parquet_error)?;
+        Ok(())
+    }
+}
+
+impl RowWriter for ParquetWriter {
+    fn write(&mut self, value: Value) -> Result<(), LabeledError> {
+        self.rows.push(expect_record(value)?);
+        match self.rows.len() < ROW_GROUP_SIZE {
+            true => Ok(()),
+            false => self.flush_rows(),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), LabeledError> {
+        if !self.rows.is_empty() || self.writer.is_none() {
+            self.flush_rows()?;
+        }
+        match self.writer.take() {
+            Some(writer) => writer.close().map(|_| ()).map_err(|e| write_error(&e)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_file_path() {
+    let output = Path::new("out/extract.csv.gz");
+    assert_eq!(file_path(output, 0, false), output);
+    assert_eq!(
+        file_path(output, 1, true),
+        Path::new("out/extract_2.csv.gz")
+    );
+}
+
+#[test]
+fn test_text() {
+    let span = Span::unknown();
+    assert_eq!(text(Value::nothing(span)), "");
+    assert_eq!(text(Value::binary(vec![0xab], span)), "ab");
+    let list = Value::list(vec![Value::int(1, span), Value::string("a", span)], span);
+    assert_eq!(text(list), r#"[1,"a"]"#);
+}
+
+#[test]
+fn test_export_empty_split() {
+    let dir = std::env::temp_dir().join(format!("mssql_export_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = ExportOptions {
+        format: ExportFormat::Csv,
+        output: dir.join("extract.csv"),
+        compression: Compression::None,
+        max_rows: Some(10),
+    };
+
+    let files = export(std::iter::empty(), &options).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, dir.join("extract_1.csv"));
+    assert_eq!(files[0].rows, 0);
+    std::fs::remove_dir_all(dir).unwrap();
+}
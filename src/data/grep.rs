@@ -0,0 +1,180 @@
+use async_std::net::TcpStream;
+use fancy_regex::Regex;
+use nu_protocol::{record, LabeledError, Span, Value};
+use tiberius::{Client, ToSql};
+
+use super::{glob_match, query_records, quote_identifier, text};
+
+pub const DEFAULT_GREP_LIMIT: usize = 100;
+
+/// Searches the definitions of views, procedures, functions and triggers for
+/// `regex`, returning one record per matching line.
+pub async fn grep_definitions(
+    client: &mut Client<TcpStream>,
+    regex: &Regex,
+) -> Result<Vec<Value>, LabeledError> {
+    let modules = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS name, LOWER(o.type_desc) AS type, m.definition
+        FROM sys.sql_modules m
+        JOIN sys.objects o ON o.object_id = m.object_id
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.is_ms_shipped = 0
+        ORDER BY s.name, o.name",
+        &[],
+    )
+    .await?;
+
+    let span = Span::unknown();
+    let mut matches = vec![];
+    for module in modules {
+        let definition = text(&module, "definition");
+        for (line_number, line) in matching_lines(&definition, regex)? {
+            matches.push(Value::record(
+                record! {
+                    "name" => Value::string(text(&module, "name"), span),
+                    "type" => Value::string(text(&module, "type"), span),
+                    "line_number" => Value::int(line_number as i64, span),
+                    "line" => Value::string(line, span),
+                },
+                span,
+            ));
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Returns the 1-based numbers and text of the lines matching `regex`.
+#[allow(clippy::result_large_err)]
+fn matching_lines(definition: &str, regex: &Regex) -> Result<Vec<(usize, String)>, LabeledError> {
+    let mut lines = vec![];
+    for (index, line) in definition.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let is_match = regex
+            .is_match(line)
+            .map_err(|e| LabeledError::new(format!("Error matching {}: {e}", regex.as_str())))?;
+        if is_match {
+            lines.push((index + 1, line.to_string()));
+        }
+    }
+    Ok(lines)
+}
+
+/// Searches the string columns of the tables matching `tables` (glob patterns,
+/// all tables when empty) for rows containing `needle`.
+///
+/// Each table is searched with one generated `LIKE` query taking the needle as
+/// a parameter, returning at most `limit` rows per table.
+pub async fn grep_data(
+    client: &mut Client<TcpStream>,
+    needle: &str,
+    tables: &[String],
+    limit: usize,
+) -> Result<Vec<Value>, LabeledError> {
+    let columns = query_records(
+        client,
+        "SELECT s.name + '.' + t.name AS [table],
+            QUOTENAME(s.name) + '.' + QUOTENAME(t.name) AS quoted, c.name AS [column]
+        FROM sys.tables t
+        JOIN sys.schemas s ON s.schema_id = t.schema_id
+        JOIN sys.columns c ON c.object_id = t.object_id
+        JOIN sys.types ty ON ty.user_type_id = c.system_type_id
+        WHERE t.is_ms_shipped = 0
+            AND ty.name IN ('char', 'varchar', 'nchar', 'nvarchar', 'text', 'ntext')
+        ORDER BY s.name, t.name, c.column_id",
+        &[],
+    )
+    .await?;
+
+    let mut searched: Vec<(String, String, Vec<String>)> = vec![];
+    for column in columns {
+        let table = text(&column, "table");
+        let selected = tables.is_empty()
+            || tables.iter().any(|pattern| match pattern.contains('.') {
+                true => glob_match(pattern, &table),
+                false => glob_match(pattern, table.split_once('.').map_or("", |(_, name)| name)),
+            });
+        if !selected {
+            continue;
+        }
+        match searched.last_mut() {
+            Some((last, _, columns)) if *last == table => columns.push(text(&column, "column")),
+            _ => searched.push((
+                table,
+                text(&column, "quoted"),
+                vec![text(&column, "column")],
+            )),
+        }
+    }
+
+    let span = Span::unknown();
+    let pattern = like_pattern(needle);
+    let limit = limit as i64;
+    let params: [&dyn ToSql; 2] = [&pattern, &limit];
+    let lowercase = needle.to_lowercase();
+
+    let mut matches = vec![];
+    for (table, quoted, columns) in searched {
+        let conditions = columns
+            .iter()
+            .map(|column| format!("{} LIKE @P1 ESCAPE '\\'", quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let sql = format!("SELECT TOP (@P2) * FROM {quoted} WHERE {conditions}");
+
+        for row in query_records(client, &sql, &params).await? {
+            // LIKE follows the column collation, so this only approximates which
+            // columns matched under case or accent insensitive collations.
+            let matched: Vec<Value> = columns
+                .iter()
+                .filter(|column| {
+                    row.get_data_by_key(column)
+                        .and_then(|value| value.as_str().ok().map(str::to_lowercase))
+                        .is_some_and(|value| value.contains(&lowercase))
+                })
+                .map(|column| Value::string(column, span))
+                .collect();
+
+            matches.push(Value::record(
+                record! {
+                    "table" => Value::string(&table, span),
+                    "columns" => Value::list(matched, span),
+                    "row" => row,
+                },
+                span,
+            ));
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Builds a `LIKE` pattern matching `text` anywhere, escaping its wildcards.
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '[' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+#[test]
+fn test_like_pattern() {
+    assert_eq!(like_pattern("50%_off"), "%50\\%\\_off%");
+    assert_eq!(like_pattern("[a]"), "%\\[a]%");
+}
+
+#[test]
+fn test_matching_lines() {
+    let regex = Regex::new(r"(?i)from\s+dbo\.users").unwrap();
+    let definition = "CREATE VIEW v AS\r\nSELECT *\r\nFROM dbo.Users";
+    assert_eq!(
+        matching_lines(definition, &regex).unwrap(),
+        vec![(3, "FROM dbo.Users".to_string())]
+    );
+}
@@ -0,0 +1,321 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use nu_protocol::{LabeledError, Record, Span, Value};
+use tiberius::ColumnData;
+
+use super::{parse_json, to_row, TableColumn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+    JsonLines,
+}
+
+impl ImportFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(LabeledError::new(format!(
+                "Unknown import format {format}, expected csv, tsv or jsonl"
+            ))),
+        }
+    }
+
+    /// The format implied by the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+enum Source {
+    Delimited {
+        reader: csv::Reader<BufReader<File>>,
+        /// The column each field is loaded into.
+        columns: Vec<String>,
+    },
+    JsonLines {
+        lines: Lines<BufReader<File>>,
+        line: u64,
+    },
+}
+
+/// A row read from the file, before it is converted to the table's types.
+struct SourceRow {
+    line: u64,
+    record: Result<Record, String>,
+    /// The row as written to the reject file.
+    raw: serde_json::Value,
+}
+
+/// Streams the rows of a file converted to the [`super::insert_columns`] of a
+/// table, writing rows that cannot be read or converted to a reject file
+/// instead of stopping the load.
+///
+/// Empty CSV and TSV fields are loaded as NULL, and hex strings, with or
+/// without a `0x` prefix, are accepted for binary columns.
+pub struct ImportRows<'a> {
+    source: Source,
+    columns: &'a [TableColumn],
+    reject_path: PathBuf,
+    rejects: Option<BufWriter<File>>,
+    pub rejected: u64,
+}
+
+impl<'a> ImportRows<'a> {
+    #[allow(clippy::result_large_err)]
+    pub fn open(
+        path: &Path,
+        format: ImportFormat,
+        columns: &'a [TableColumn],
+        reject_path: PathBuf,
+    ) -> Result<Self, LabeledError> {
+        let file = File::open(path)
+            .map_err(|e| LabeledError::new(format!("Error opening {}: {e}", path.display())))?;
+        let reader = BufReader::new(file);
+
+        let source = match format {
+            ImportFormat::JsonLines => Source::JsonLines {
+                lines: reader.lines(),
+                line: 0,
+            },
+            format => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(if format == ImportFormat::Tsv {
+                        b'\t'
+                    } else {
+                        b','
+                    })
+                    .from_reader(reader);
+                let headers = reader.headers().map_err(|e| {
+                    LabeledError::new(format!("Error reading {}: {e}", path.display()))
+                })?;
+                let columns = headers
+                    .iter()
+                    .map(|header| {
+                        columns
+                            .iter()
+                            .find(|column| column.name.eq_ignore_ascii_case(header.trim()))
+                            .map(|column| column.name.clone())
+                            .ok_or_else(|| {
+                                LabeledError::new(format!(
+                                    "Column {header} does not exist or cannot be loaded"
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Source::Delimited { reader, columns }
+            }
+        };
+
+        Ok(Self {
+            source,
+            columns,
+            reject_path,
+            rejects: None,
+            rejected: 0,
+        })
+    }
+
+    /// The reject file, if any row was rejected.
+    pub fn reject_path(&self) -> Option<&Path> {
+        self.rejects.as_ref().map(|_| self.reject_path.as_path())
+    }
+
+    /// Flushes the reject file.
+    #[allow(clippy::result_large_err)]
+    pub fn finish(&mut self) -> Result<(), LabeledError> {
+        match &mut self.rejects {
+            Some(rejects) => rejects.flush().map_err(|e| self.reject_error(&e)),
+            None => Ok(()),
+        }
+    }
+
+    fn reject_error(&self, e: &dyn std::fmt::Display) -> LabeledError {
+        LabeledError::new(format!(
+            "Error writing rejected rows to {}: {e}",
+            self.reject_path.display()
+        ))
+    }
+
+    /// Writes a row to the reject file as a JSON line of `{line, error, row}`.
+    #[allow(clippy::result_large_err)]
+    fn reject(&mut self, row: SourceRow, error: String) -> Result<(), LabeledError> {
+        let rejects = match &mut self.rejects {
+            Some(rejects) => rejects,
+            None => {
+                let file = File::create(&self.reject_path).map_err(|e| self.reject_error(&e))?;
+                self.rejects.insert(BufWriter::new(file))
+            }
+        };
+        let line = serde_json::json!({ "line": row.line, "error": error, "row": row.raw });
+        let written = writeln!(rejects, "{line}");
+        written.map_err(|e| self.reject_error(&e))?;
+        self.rejected += 1;
+        Ok(())
+    }
+
+    fn next_source_row(&mut self) -> Option<Result<SourceRow, LabeledError>> {
+        match &mut self.source {
+            Source::Delimited { reader, columns } => {
+                let mut fields = csv::StringRecord::new();
+                match reader.read_record(&mut fields) {
+                    Ok(false) => None,
+                    Ok(true) => {
+                        let line = fields.position().map_or(0, |position| position.line());
+                        let raw = columns
+                            .iter()
+                            .zip(fields.iter())
+                            .map(|(column, field)| (column.clone(), field.into()))
+                            .collect();
+                        let record = match fields.len() == columns.len() {
+                            true => Ok(columns
+                                .iter()
+                                .zip(fields.iter())
+                                .map(|(column, field)| {
+                                    let value = match field {
+                                        "" => Value::nothing(Span::unknown()),
+                                        field => Value::string(field, Span::unknown()),
+                                    };
+                                    (column.clone(), value)
+                                })
+                                .collect()),
+                            false => Err(format!(
+                                "Expected {} fields but found {}",
+                                columns.len(),
+                                fields.len()
+                            )),
+                        };
+                        Some(Ok(SourceRow {
+                            line,
+                            record,
+                            raw: serde_json::Value::Object(raw),
+                        }))
+                    }
+                    Err(e) => match e.kind() {
+                        csv::ErrorKind::Io(_) => {
+                            Some(Err(LabeledError::new(format!("Error reading file: {e}"))))
+                        }
+                        _ => Some(Ok(SourceRow {
+                            line: e.position().map_or(0, |position| position.line()),
+                            record: Err(e.to_string()),
+                            raw: serde_json::Value::Null,
+                        })),
+                    },
+                }
+            }
+            Source::JsonLines { lines, line } => loop {
+                let text = match lines.next()? {
+                    Ok(text) => text,
+                    Err(e) => {
+                        return Some(Err(LabeledError::new(format!("Error reading file: {e}"))))
+                    }
+                };
+                *line += 1;
+                if text.trim().is_empty() {
+                    continue;
+                }
+
+                let record = match parse_json(&text) {
+                    Ok(Value::Record { val, .. }) => Ok(val.into_owned()),
+                    Ok(value) => Err(format!("Expected a record but got {}", value.get_type())),
+                    Err(e) => Err(format!("Invalid JSON: {e}")),
+                };
+                return Some(Ok(SourceRow {
+                    line: *line,
+                    record,
+                    raw: serde_json::Value::String(text),
+                }));
+            },
+        }
+    }
+
+    /// Converts the fields of a record to cells for the table's columns.
+    fn convert(&self, mut record: Record) -> Result<Vec<ColumnData<'static>>, String> {
+        for column in self.columns {
+            if !matches!(column.sql_type.as_str(), "binary" | "varbinary" | "image") {
+                continue;
+            }
+            let field = record
+                .iter_mut()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&column.name));
+            if let Some((_, value @ Value::String { .. })) = field {
+                let text = value.as_str().unwrap_or_default();
+                let hex_digits = text.strip_prefix("0x").unwrap_or(text);
+                if let Ok(bytes) = hex::decode(hex_digits) {
+                    *value = Value::binary(bytes, Span::unknown());
+                }
+            }
+        }
+
+        to_row(&Value::record(record, Span::unknown()), self.columns).map_err(|e| e.msg)
+    }
+}
+
+impl Iterator for ImportRows<'_> {
+    type Item = Result<Vec<ColumnData<'static>>, LabeledError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut row = match self.next_source_row()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let record = std::mem::replace(&mut row.record, Ok(Record::new()));
+            match record.and_then(|record| self.convert(record)) {
+                Ok(cells) => return Some(Ok(cells)),
+                Err(error) => {
+                    if let Err(e) = self.reject(row, error) {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_import_rows() {
+    let column = |name: &str, sql_type: &str| TableColumn {
+        name: name.to_string(),
+        sql_type: sql_type.to_string(),
+        max_length: -1,
+        precision: 0,
+        scale: 0,
+        nullable: true,
+        identity: false,
+        computed: false,
+    };
+    let columns = [column("Id", "int"), column("Data", "varbinary")];
+
+    let dir = std::env::temp_dir().join(format!("mssql_import_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("rows.csv");
+    std::fs::write(&path, "id,data\n1,0x0102\nx,\n3,\n").unwrap();
+    let reject_path = dir.join("rows.csv.rejects.jsonl");
+
+    let mut rows = ImportRows::open(&path, ImportFormat::Csv, &columns, reject_path).unwrap();
+    let loaded: Vec<_> = rows.by_ref().collect::<Result<_, _>>().unwrap();
+    rows.finish().unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0][1], ColumnData::Binary(Some(vec![1, 2].into())));
+    assert_eq!(loaded[1][1], ColumnData::Binary(None));
+    assert_eq!(rows.rejected, 1);
+
+    let rejects = std::fs::read_to_string(rows.reject_path().unwrap()).unwrap();
+    assert!(rejects.starts_with(r#"{"line":3,"error":"Cannot convert string to int"#));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,211 @@
+use chrono::{DateTime, FixedOffset, Timelike};
+use nu_protocol::{LabeledError, Value};
+
+use super::{quote_identifier, quote_name, to_json};
+
+/// SQL Server accepts at most this many row value expressions per `VALUES`.
+pub const MAX_INSERT_BATCH_SIZE: usize = 1000;
+
+/// Builds a script of `INSERT ... VALUES` statements that insert `rows` into
+/// `table`, `batch_size` rows per statement. The table name is quoted with
+/// [`quote_name`].
+///
+/// Columns appear in the order their fields are first seen, and fields a row
+/// leaves out are inserted as NULL. With `identity_insert` the statements are
+/// wrapped in `SET IDENTITY_INSERT` so identity values can be given.
+#[allow(clippy::result_large_err)]
+pub fn insert_script(
+    table: &str,
+    rows: &[Value],
+    batch_size: usize,
+    identity_insert: bool,
+) -> Result<String, LabeledError> {
+    let mut columns: Vec<&str> = vec![];
+    for row in rows {
+        let record = match row {
+            Value::Record { val, .. } => val,
+            Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+            other => {
+                return Err(LabeledError::new(format!(
+                    "Expected a table but got {}",
+                    other.get_type()
+                ))
+                .with_label("not a record", other.span()))
+            }
+        };
+        for name in record.columns() {
+            if !columns.contains(&name.as_str()) {
+                columns.push(name);
+            }
+        }
+    }
+
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let table = quote_name(table);
+    let column_list = columns
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut statements = vec![];
+    for batch in rows.chunks(batch_size.clamp(1, MAX_INSERT_BATCH_SIZE)) {
+        let values = batch
+            .iter()
+            .map(|row| {
+                let literals = columns
+                    .iter()
+                    .map(|column| match row.get_data_by_key(column) {
+                        Some(value) => sql_literal(value).map_err(|e| {
+                            LabeledError::new(format!("{} in column {column}", e.msg))
+                                .with_label("cannot be scripted", row.span())
+                        }),
+                        None => Ok("NULL".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("    ({})", literals.join(", ")))
+            })
+            .collect::<Result<Vec<_>, LabeledError>>()?;
+        statements.push(format!(
+            "INSERT INTO {table} ({column_list}) VALUES\n{};",
+            values.join(",\n")
+        ));
+    }
+
+    if identity_insert {
+        statements.insert(0, format!("SET IDENTITY_INSERT {table} ON;"));
+        statements.push(format!("SET IDENTITY_INSERT {table} OFF;"));
+    }
+    Ok(statements.join("\n"))
+}
+
+/// Writes a value as a T-SQL literal.
+///
+/// Durations are written as nanoseconds, the BIGINT `mssql create-table`
+/// declares for them, since the column types are not known here. Records and
+/// lists are written as JSON text.
+#[allow(clippy::result_large_err)]
+pub fn sql_literal(value: Value) -> Result<String, LabeledError> {
+    let literal = match value {
+        Value::Nothing { .. } => "NULL".to_string(),
+        Value::Bool { val, .. } => (val as u8).to_string(),
+        Value::Int { val, .. } | Value::Filesize { val, .. } | Value::Duration { val, .. } => {
+            val.to_string()
+        }
+        Value::Float { val, .. } if !val.is_finite() => {
+            return Err(LabeledError::new(format!(
+                "Cannot write {val} as a SQL Server float"
+            )))
+        }
+        // Plain digits are read as a decimal, which cannot hold more than 38.
+        Value::Float { val, .. } if val != 0.0 && !(1e-5..1e15).contains(&val.abs()) => {
+            format!("{val:e}")
+        }
+        Value::Float { val, .. } => val.to_string(),
+        Value::String { val, .. } | Value::Glob { val, .. } => string_literal(&val),
+        Value::Binary { val, .. } => format!("0x{}", hex::encode_upper(val)),
+        Value::Date { val, .. } => date_literal(val),
+        value @ (Value::Record { .. } | Value::List { .. }) => {
+            string_literal(&to_json(value).to_string())
+        }
+        Value::Error { error, .. } => return Err(LabeledError::from(*error)),
+        other => {
+            return Err(LabeledError::new(format!(
+                "Cannot write {} as a SQL literal",
+                other.get_type()
+            )))
+        }
+    };
+    Ok(literal)
+}
+
+fn string_literal(text: &str) -> String {
+    format!("N'{}'", text.replace('\'', "''"))
+}
+
+/// Writes a date in ISO 8601 form with as many fractional digits as it needs.
+///
+/// Literals with more precision than `datetime` keeps, or with an offset
+/// other than UTC, are cast so they can still be inserted into any date and
+/// time column.
+fn date_literal(date: DateTime<FixedOffset>) -> String {
+    let mut text = date.format("%Y-%m-%dT%H:%M:%S").to_string();
+    let fraction = format!("{:07}", date.nanosecond().min(999_999_999) / 100);
+    let fraction = fraction.trim_end_matches('0');
+    if !fraction.is_empty() {
+        text = format!("{text}.{fraction}");
+    }
+
+    if date.offset().local_minus_utc() != 0 {
+        format!("CAST('{text}{}' AS datetimeoffset(7))", date.format("%:z"))
+    } else if fraction.len() > 3 {
+        format!("CAST('{text}' AS datetime2(7))")
+    } else {
+        format!("'{text}'")
+    }
+}
+
+#[test]
+fn test_insert_script() {
+    use chrono::TimeZone;
+    use nu_protocol::{record, Span};
+
+    let span = Span::unknown();
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let rows = vec![
+        Value::record(
+            record! {
+                "UserID" => Value::int(1, span),
+                "FirstName" => Value::string("D'Arcy", span),
+                "DateOfBirth" => Value::date(utc.with_ymd_and_hms(1990, 5, 1, 0, 0, 0).unwrap(), span),
+            },
+            span,
+        ),
+        Value::record(
+            record! {
+                "UserID" => Value::int(2, span),
+                "Active" => Value::bool(true, span),
+                "Avatar" => Value::binary(vec![0xAB, 0x01], span),
+            },
+            span,
+        ),
+        Value::record(record! { "UserID" => Value::int(3, span) }, span),
+    ];
+
+    let script = insert_script("dbo.Users", &rows, 2, true).unwrap();
+    assert_eq!(
+        script,
+        "SET IDENTITY_INSERT [dbo].[Users] ON;
+INSERT INTO [dbo].[Users] ([UserID], [FirstName], [DateOfBirth], [Active], [Avatar]) VALUES
+    (1, N'D''Arcy', '1990-05-01T00:00:00', NULL, NULL),
+    (2, NULL, NULL, 1, 0xAB01);
+INSERT INTO [dbo].[Users] ([UserID], [FirstName], [DateOfBirth], [Active], [Avatar]) VALUES
+    (3, NULL, NULL, NULL, NULL);
+SET IDENTITY_INSERT [dbo].[Users] OFF;"
+    );
+
+    let literal = |value| sql_literal(value).unwrap();
+    let offset = FixedOffset::east_opt(3600).unwrap();
+    let precise = utc
+        .with_ymd_and_hms(2024, 1, 2, 3, 4, 5)
+        .unwrap()
+        .with_nanosecond(123_456_700)
+        .unwrap();
+    assert_eq!(
+        literal(Value::date(precise, span)),
+        "CAST('2024-01-02T03:04:05.1234567' AS datetime2(7))"
+    );
+    assert_eq!(
+        literal(Value::date(
+            offset.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+            span
+        )),
+        "CAST('2024-01-02T03:04:05+01:00' AS datetimeoffset(7))"
+    );
+    assert_eq!(literal(Value::float(2.5, span)), "2.5");
+    assert_eq!(literal(Value::float(1e300, span)), "1e300");
+    assert!(sql_literal(Value::float(f64::NAN, span)).is_err());
+}
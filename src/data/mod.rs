@@ -1,11 +1,55 @@
+mod bulk;
+mod catalog;
 mod connection;
+mod create_table;
+mod data_diff;
 mod db;
+mod depends;
+mod describe;
+mod er_diagram;
+mod exec;
+mod export;
+mod grep;
+mod import;
+mod insert_script;
 mod connection_args;
 mod connection_pool;
+mod procedure;
 mod query_source;
+mod result_parser;
+mod result_schema;
+mod schema;
+mod schema_diff;
+mod script;
+mod to_sql;
+mod udt;
+mod upsert;
+mod xml;
 
+pub use bulk::*;
+pub use catalog::*;
 pub use connection::*;
+pub use create_table::*;
+pub use data_diff::*;
 pub use db::*;
+pub use depends::*;
+pub use describe::*;
+pub use er_diagram::*;
+pub use exec::*;
+pub use export::*;
+pub use grep::*;
+pub use import::*;
+pub use insert_script::*;
 pub use connection_args::*;
 pub use connection_pool::*;
-pub use query_source::*;
\ No newline at end of file
+pub use procedure::*;
+pub use query_source::*;
+pub use result_parser::*;
+pub use result_schema::*;
+pub use schema::*;
+pub use schema_diff::*;
+pub use script::*;
+pub use to_sql::*;
+pub use udt::*;
+pub use upsert::*;
+pub use xml::*;
\ No newline at end of file
@@ -0,0 +1,291 @@
+use async_std::{net::TcpStream, stream::StreamExt};
+use nu_protocol::{record, LabeledError, Record, Span, Value};
+use tiberius::{Client, ColumnData, Query, ToSql};
+
+use super::{
+    collect_results, infer_column_data, parse_value, quote_name, to_column_data, ParseOptions,
+    ResultReader, SqlParam, TableColumn,
+};
+
+/// A procedure parameter as described by the `sys.parameters` catalog view.
+#[derive(Debug, Clone)]
+struct ProcParameter {
+    /// The parameter name without the leading `@`.
+    name: String,
+    column: TableColumn,
+    is_output: bool,
+}
+
+/// The outcome of a procedure call.
+pub struct ProcResult {
+    pub return_value: Value,
+    pub output: Record,
+    pub results: Vec<Value>,
+}
+
+impl ProcResult {
+    pub fn into_value(self, span: Span) -> Value {
+        Value::record(
+            record! {
+                "return_value" => self.return_value,
+                "output" => Value::record(self.output, span),
+                "results" => Value::list(self.results, span),
+            },
+            span,
+        )
+    }
+}
+
+async fn proc_parameters(
+    client: &mut Client<TcpStream>,
+    procedure: &str,
+) -> Result<Vec<ProcParameter>, LabeledError> {
+    let mut query = Query::new(
+        "SELECT OBJECT_ID(@P1) AS object_id, p.name,
+            CASE WHEN t.is_user_defined = 1 AND t.is_assembly_type = 0
+                THEN TYPE_NAME(p.system_type_id) ELSE t.name END AS type_name,
+            p.max_length, p.precision, p.scale, p.is_output, t.is_table_type
+        FROM (SELECT 1 AS one) AS x
+        LEFT JOIN sys.parameters p ON p.object_id = OBJECT_ID(@P1) AND p.parameter_id > 0
+        LEFT JOIN sys.types t ON t.user_type_id = p.user_type_id
+        ORDER BY p.parameter_id",
+    );
+    query.bind(procedure);
+
+    let mut stream = query
+        .query(client)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error reading parameters of {procedure}: {e}")))?
+        .into_row_stream();
+
+    let mut parameters = vec![];
+    let mut exists = false;
+    while let Some(row) = stream.next().await {
+        let row = row.map_err(|e| LabeledError::new(format!("Error reading parameters: {e}")))?;
+        exists |= row.get::<i32, _>(0).is_some();
+
+        let Some(name) = row.get::<&str, _>(1) else {
+            continue;
+        };
+        if row.get::<bool, _>(7).unwrap_or_default() {
+            return Err(LabeledError::new(format!(
+                "Parameter {name} is a table-valued parameter, which is not supported"
+            )));
+        }
+
+        parameters.push(ProcParameter {
+            name: name.trim_start_matches('@').to_string(),
+            column: TableColumn {
+                name: name.to_string(),
+                sql_type: match row.get::<&str, _>(2).unwrap_or_default() {
+                    "sysname" => "nvarchar".to_string(),
+                    other => other.to_string(),
+                },
+                max_length: row.get(3).unwrap_or_default(),
+                precision: row.get(4).unwrap_or_default(),
+                scale: row.get(5).unwrap_or_default(),
+                nullable: true,
+                identity: false,
+                computed: false,
+            },
+            is_output: row.get(6).unwrap_or_default(),
+        });
+    }
+
+    if !exists {
+        return Err(LabeledError::new(format!(
+            "Procedure {procedure} does not exist"
+        )));
+    }
+
+    Ok(parameters)
+}
+
+/// Converts an input value to the parameter's declared type where possible.
+///
+/// Types without a direct conversion are sent as inferred from the value and
+/// converted by the server.
+#[allow(clippy::result_large_err)]
+fn parameter_value(
+    value: &Value,
+    parameter: &ProcParameter,
+) -> Result<ColumnData<'static>, LabeledError> {
+    match parameter.column.sql_type.as_str() {
+        "sql_variant" | "hierarchyid" | "geography" | "geometry" => infer_column_data(value),
+        _ => to_column_data(value, &parameter.column),
+    }
+}
+
+/// Calls `procedure`, binding `inputs` to its parameters by name. Result sets
+/// are read into records as `mssql query` reads them under `options`.
+///
+/// Output parameters are captured into variables so their final values can be
+/// read back along with the procedure's return code.
+#[allow(clippy::result_large_err)]
+pub async fn call_procedure(
+    client: &mut Client<TcpStream>,
+    procedure: &str,
+    inputs: &Record,
+    options: ParseOptions,
+) -> Result<ProcResult, LabeledError> {
+    let parameters = proc_parameters(client, procedure).await?;
+
+    for (name, value) in inputs.iter() {
+        let name = name.trim_start_matches('@');
+        if !parameters
+            .iter()
+            .any(|parameter| parameter.name.eq_ignore_ascii_case(name))
+        {
+            return Err(LabeledError::new(format!(
+                "Procedure {procedure} has no parameter @{name}"
+            ))
+            .with_label("unknown parameter", value.span()));
+        }
+    }
+
+    let (sql, params) = call_batch(procedure, &parameters, inputs)?;
+    let params: Vec<&dyn ToSql> = params.iter().map(|p| p as &dyn ToSql).collect();
+    let stream = client
+        .query(sql, &params)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error calling {procedure}: {e}")))?;
+    let mut results = collect_results(stream)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error calling {procedure}: {e}")))?;
+
+    // The return code and output parameters are always the last result set.
+    let status = results.pop().and_then(|(_, mut rows)| rows.pop());
+    let mut cells = status
+        .iter()
+        .flat_map(|row| row.cells().map(|(_, cell)| cell));
+
+    let return_value = match cells.next() {
+        Some(cell) => parse_value(cell)?,
+        None => Value::nothing(Span::unknown()),
+    };
+
+    let mut output = Record::new();
+    for parameter in parameters.iter().filter(|parameter| parameter.is_output) {
+        let value = match cells.next() {
+            Some(cell) => parse_value(cell)?,
+            None => Value::nothing(Span::unknown()),
+        };
+        output.push(parameter.name.clone(), value);
+    }
+
+    let mut reader = ResultReader::new(options);
+    let mut sets = vec![];
+    for (columns, rows) in &results {
+        let mut values = reader.metadata(columns)?;
+        for row in rows {
+            values.extend(reader.row(row)?);
+        }
+        values.extend(reader.finish()?);
+        sets.push(Value::list(values, Span::unknown()));
+    }
+
+    Ok(ProcResult {
+        return_value,
+        output,
+        results: sets,
+    })
+}
+
+/// The batch that calls `procedure` with `inputs` and selects its return code
+/// and output parameters, along with the values bound to it.
+#[allow(clippy::result_large_err)]
+fn call_batch(
+    procedure: &str,
+    parameters: &[ProcParameter],
+    inputs: &Record,
+) -> Result<(String, Vec<SqlParam>), LabeledError> {
+    let input_for = |parameter: &ProcParameter| {
+        inputs
+            .iter()
+            .find(|(name, _)| {
+                name.trim_start_matches('@')
+                    .eq_ignore_ascii_case(&parameter.name)
+            })
+            .map(|(_, value)| value)
+    };
+
+    let mut sql = String::from("DECLARE @mssql_return INT;\n");
+    let mut arguments = vec![];
+    let mut params: Vec<SqlParam> = vec![];
+
+    for (index, parameter) in parameters.iter().enumerate() {
+        let input = input_for(parameter);
+        if parameter.is_output {
+            let variable = format!("@mssql_out{index}");
+            sql.push_str(&format!(
+                "DECLARE {variable} {};\n",
+                parameter.column.type_declaration()
+            ));
+            if let Some(value) = input {
+                params.push(SqlParam(parameter_value(value, parameter)?));
+                sql.push_str(&format!("SET {variable} = @P{};\n", params.len()));
+            }
+            arguments.push(format!("@{} = {variable} OUTPUT", parameter.name));
+        } else if let Some(value) = input {
+            params.push(SqlParam(parameter_value(value, parameter)?));
+            arguments.push(format!("@{} = @P{}", parameter.name, params.len()));
+        }
+    }
+
+    let outputs: Vec<String> = parameters
+        .iter()
+        .enumerate()
+        .filter(|(_, parameter)| parameter.is_output)
+        .map(|(index, _)| format!(", @mssql_out{index}"))
+        .collect();
+
+    sql.push_str(&format!(
+        "EXEC @mssql_return = {} {};\nSELECT @mssql_return{};",
+        quote_name(procedure),
+        arguments.join(", "),
+        outputs.concat()
+    ));
+    Ok((sql, params))
+}
+
+#[test]
+fn test_call_batch() {
+    let parameter = |name: &str, sql_type: &str, max_length: i16, is_output: bool| ProcParameter {
+        name: name.to_string(),
+        column: TableColumn {
+            name: format!("@{name}"),
+            sql_type: sql_type.to_string(),
+            max_length,
+            precision: 0,
+            scale: 0,
+            nullable: true,
+            identity: false,
+            computed: false,
+        },
+        is_output,
+    };
+    let parameters = [
+        parameter("Id", "int", 4, false),
+        parameter("Skipped", "int", 4, false),
+        parameter("Name", "nvarchar", 100, true),
+        parameter("Count", "int", 4, true),
+    ];
+    let span = Span::test_data();
+    let inputs = record! {
+        "@id" => Value::int(7, span),
+        "Name" => Value::string("seven", span),
+    };
+
+    let (sql, params) = call_batch("dbo.Get Things", &parameters, &inputs).unwrap();
+    assert_eq!(
+        sql,
+        "DECLARE @mssql_return INT;
+DECLARE @mssql_out2 nvarchar(50);
+SET @mssql_out2 = @P2;
+DECLARE @mssql_out3 int;
+EXEC @mssql_return = [dbo].[Get Things] @Id = @P1, @Name = @mssql_out2 OUTPUT, \
+@Count = @mssql_out3 OUTPUT;
+SELECT @mssql_return, @mssql_out2, @mssql_out3;"
+    );
+    assert_eq!(params.len(), 2);
+}
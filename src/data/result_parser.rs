@@ -0,0 +1,602 @@
+use base64::Engine;
+use nu_protocol::{LabeledError, Record, ShellError, Signature, Span, SyntaxShape, Value};
+use tiberius::{Column, ColumnData, ColumnType, Row};
+
+use super::{
+    decode_wrapped, format_time, parse_value, parse_xml, time_nanoseconds, SpatialFormat,
+    WrappedType,
+};
+
+/// The column name SQL Server gives the result of a `FOR JSON` query.
+const FOR_JSON_COLUMN: &str = "JSON_F52E2B61-18A1-11d1-B105-00805F49916B";
+
+/// The column name SQL Server gives the result of a `FOR XML` query.
+const FOR_XML_COLUMN: &str = "XML_F52E2B61-18A1-11d1-B105-00805F49916B";
+
+/// Options controlling how result values are converted to Nushell values.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Columns whose strings are parsed as JSON. An empty list parses every
+    /// string that holds a JSON object or array.
+    pub parse_json: Option<Vec<String>>,
+    /// Whether XML columns and `FOR XML` results are parsed into `from xml` records.
+    pub parse_xml: bool,
+    /// How `geometry` and `geography` columns are returned.
+    pub spatial: SpatialFormat,
+    /// How `uniqueidentifier` values are written.
+    pub guid: GuidFormat,
+    /// How binary values are returned.
+    pub binary: BinaryFormat,
+    /// Whether the padding of fixed length `char` and `nchar` values is trimmed.
+    pub trim_char: bool,
+    /// How `time` values are returned.
+    pub time: TimeFormat,
+    /// Pairs of `date` and `time` columns combined into one datetime, which
+    /// takes the place of the `date` column.
+    pub combine_datetime: Vec<(String, String)>,
+    /// Columns that were converted by [`super::unwrap_types`] and are decoded
+    /// back to their own type.
+    pub wrapped: Vec<(String, WrappedType)>,
+    /// How columns that share a name are told apart.
+    pub duplicate_columns: DuplicateColumns,
+    /// The source table of each column of the first result set, read by
+    /// [`super::prepare_query`] for [`DuplicateColumns::PrefixTable`].
+    pub source_tables: Vec<Option<String>>,
+}
+
+impl ParseOptions {
+    #[allow(clippy::result_large_err)]
+    pub fn from_call(call: &nu_plugin::EvaluatedCall) -> Result<ParseOptions, LabeledError> {
+        Ok(ParseOptions {
+            parse_json: call.get_flag("parse-json")?,
+            parse_xml: call.has_flag("parse-xml")?,
+            spatial: match call.get_flag::<String>("spatial")? {
+                Some(format) => SpatialFormat::parse(&format)?,
+                None => SpatialFormat::default(),
+            },
+            guid: match call.get_flag::<String>("guid")? {
+                Some(format) => GuidFormat::parse(&format)?,
+                None => GuidFormat::default(),
+            },
+            binary: match call.get_flag::<String>("binary")? {
+                Some(format) => BinaryFormat::parse(&format)?,
+                None => BinaryFormat::default(),
+            },
+            trim_char: call.has_flag("trim-char")?,
+            time: match call.get_flag::<String>("time")? {
+                Some(format) => TimeFormat::parse(&format)?,
+                None => TimeFormat::default(),
+            },
+            combine_datetime: match call.get_flag::<Value>("combine-datetime")? {
+                Some(pairs) => pairs
+                    .as_record()?
+                    .iter()
+                    .map(|(date, time)| Ok((date.clone(), time.as_str()?.to_string())))
+                    .collect::<Result<_, ShellError>>()?,
+                None => vec![],
+            },
+            wrapped: vec![],
+            duplicate_columns: match call.get_flag::<String>("duplicate-columns")? {
+                Some(policy) => DuplicateColumns::parse(&policy)?,
+                None => DuplicateColumns::default(),
+            },
+            source_tables: vec![],
+        })
+    }
+
+    /// Converts a cell of a result row, applying the options for its column.
+    #[allow(clippy::result_large_err)]
+    pub fn parse_cell(
+        &self,
+        column: &Column,
+        data: &ColumnData<'static>,
+    ) -> Result<Value, LabeledError> {
+        if let (true, ColumnData::Xml(Some(xml))) = (self.parse_xml, data) {
+            return parse_xml(&xml.to_string()).map_err(|e| {
+                LabeledError::new(format!("Column {} is not valid XML: {e}", column.name()))
+            });
+        }
+
+        let value = parse_value(data)?;
+
+        if let Some((_, ty)) = self.wrapped.iter().find(|(name, _)| name == column.name()) {
+            return decode_wrapped(*ty, value, self.spatial).map_err(|e| {
+                LabeledError::new(format!("Error decoding column {}: {e}", column.name()))
+            });
+        }
+
+        let value = match (data, value) {
+            (ColumnData::Time(Some(time)), value) if self.time == TimeFormat::String => {
+                Value::string(format_time(time), value.span())
+            }
+            (ColumnData::Guid(Some(guid)), value) => {
+                Value::string(self.guid.format(guid), value.span())
+            }
+            (_, Value::Binary { val, internal_span }) => self.binary.format(val, internal_span),
+            (_, Value::String { val, internal_span })
+                if self.trim_char
+                    && matches!(
+                        column.column_type(),
+                        ColumnType::BigChar | ColumnType::NChar
+                    ) =>
+            {
+                Value::string(val.trim_end_matches(' '), internal_span)
+            }
+            (_, value) => value,
+        };
+
+        match (&self.parse_json, &value) {
+            (Some(columns), Value::String { val, .. }) if columns.is_empty() => {
+                let text = val.trim_start();
+                match text.starts_with('{') || text.starts_with('[') {
+                    true => Ok(parse_json(text).unwrap_or(value)),
+                    false => Ok(value),
+                }
+            }
+            (Some(columns), Value::String { val, .. })
+                if columns
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(column.name())) =>
+            {
+                parse_json(val).map_err(|e| {
+                    LabeledError::new(format!("Column {} is not valid JSON: {e}", column.name()))
+                })
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// Converts a row into a record with one field per column, named by
+    /// [`column_names`].
+    #[allow(clippy::result_large_err)]
+    pub fn parse_row(&self, row: &Row, names: &[String]) -> Result<Value, LabeledError> {
+        let mut record = Record::new();
+        let mut times = vec![];
+        for ((column, cell), name) in row.cells().zip(names) {
+            match self
+                .combine_datetime
+                .iter()
+                .find(|(_, time)| time == column.name())
+            {
+                Some(pair) => times.push((pair, cell)),
+                None => record.push(name, self.parse_cell(column, cell)?),
+            }
+        }
+
+        for ((date, time), cell) in times {
+            let combined = match (record.get(date), cell) {
+                (Some(Value::Date { val, internal_span }), ColumnData::Time(Some(value))) => {
+                    let nanoseconds = chrono::Duration::nanoseconds(time_nanoseconds(value)?);
+                    Value::date(*val + nanoseconds, *internal_span)
+                }
+                (Some(Value::Nothing { .. }), _) | (Some(_), ColumnData::Time(None)) => {
+                    Value::nothing(Span::unknown())
+                }
+                _ => {
+                    return Err(LabeledError::new(format!(
+                        "Columns {date} and {time} cannot be combined into a datetime"
+                    )))
+                }
+            };
+            record.insert(date, combined);
+        }
+
+        Ok(Value::record(record, Span::unknown()))
+    }
+}
+
+/// How columns that share a name are told apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateColumns {
+    /// `Id`, `Id_1`, `Id_2`, ...
+    #[default]
+    Rename,
+    /// Fail on the first result set with a repeated name.
+    Error,
+    /// `Users.Id`, `Orders.Id`, using the table each column is read from.
+    PrefixTable,
+}
+
+impl DuplicateColumns {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(policy: &str) -> Result<Self, LabeledError> {
+        match policy.to_lowercase().as_str() {
+            "rename" => Ok(Self::Rename),
+            "error" => Ok(Self::Error),
+            "prefix-table" => Ok(Self::PrefixTable),
+            _ => Err(LabeledError::new(format!(
+                "Unknown duplicate column policy {policy}, expected rename, error or prefix-table"
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rename => "rename",
+            Self::Error => "error",
+            Self::PrefixTable => "prefix-table",
+        }
+    }
+}
+
+/// The record field name of each column of a result set, in SELECT order.
+///
+/// Unnamed columns are called `column` followed by their position, counting
+/// from 1. Repeated names are handled by `policy`, with `tables` holding the
+/// source table of each column for [`DuplicateColumns::PrefixTable`]. Any name
+/// that is still taken gets a `_1`, `_2`, ... suffix, so no column overwrites
+/// another.
+#[allow(clippy::result_large_err)]
+pub fn column_names(
+    columns: &[Column],
+    policy: DuplicateColumns,
+    tables: &[Option<String>],
+) -> Result<Vec<String>, LabeledError> {
+    let base: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| match column.name() {
+            "" => format!("column{}", i + 1),
+            name => name.to_string(),
+        })
+        .collect();
+    let repeated = |name: &str| base.iter().filter(|other| *other == name).count() > 1;
+
+    let mut names: Vec<String> = Vec::with_capacity(columns.len());
+    for (i, name) in base.iter().enumerate() {
+        let name = match (policy, repeated(name), tables.get(i)) {
+            (DuplicateColumns::Error, true, _) => {
+                return Err(
+                    LabeledError::new(format!("Column {name} is returned more than once"))
+                        .with_help(
+                            "Alias the columns, or use --duplicate-columns rename or prefix-table",
+                        ),
+                )
+            }
+            (DuplicateColumns::PrefixTable, true, Some(Some(table))) => format!("{table}.{name}"),
+            _ => name.clone(),
+        };
+        let taken = |candidate: &str| names.iter().any(|name| name == candidate);
+        let name = match taken(&name) {
+            true => (1..)
+                .map(|n| format!("{name}_{n}"))
+                .find(|candidate| !taken(candidate))
+                .expect("a free name exists"),
+            false => name,
+        };
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// How `uniqueidentifier` values are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GuidFormat {
+    /// `6f9619ff-8b86-d011-b42d-00c04fc964ff`
+    #[default]
+    Lower,
+    /// `6F9619FF-8B86-D011-B42D-00C04FC964FF`
+    Upper,
+    /// `{6f9619ff-8b86-d011-b42d-00c04fc964ff}`
+    Braced,
+}
+
+impl GuidFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "lower" => Ok(Self::Lower),
+            "upper" => Ok(Self::Upper),
+            "braced" => Ok(Self::Braced),
+            _ => Err(LabeledError::new(format!(
+                "Unknown GUID format {format}, expected lower, upper or braced"
+            ))),
+        }
+    }
+
+    pub fn format(self, guid: &tiberius::Uuid) -> String {
+        match self {
+            Self::Lower => guid.hyphenated().to_string(),
+            Self::Upper => guid.hyphenated().to_string().to_uppercase(),
+            Self::Braced => guid.braced().to_string(),
+        }
+    }
+}
+
+/// How `time` values are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// A duration since midnight.
+    #[default]
+    Duration,
+    /// A time of day string with as many fractional digits as the column's scale.
+    String,
+}
+
+impl TimeFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "duration" => Ok(Self::Duration),
+            "string" => Ok(Self::String),
+            _ => Err(LabeledError::new(format!(
+                "Unknown time format {format}, expected duration or string"
+            ))),
+        }
+    }
+}
+
+/// How binary values are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryFormat {
+    #[default]
+    Binary,
+    /// A lowercase hex string without a `0x` prefix.
+    Hex,
+    Base64,
+}
+
+impl BinaryFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "binary" => Ok(Self::Binary),
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            _ => Err(LabeledError::new(format!(
+                "Unknown binary format {format}, expected binary, hex or base64"
+            ))),
+        }
+    }
+
+    pub fn format(self, bytes: Vec<u8>, span: Span) -> Value {
+        match self {
+            Self::Binary => Value::binary(bytes, span),
+            Self::Hex => Value::string(hex::encode(bytes), span),
+            Self::Base64 => Value::string(
+                base64::engine::general_purpose::STANDARD.encode(bytes),
+                span,
+            ),
+        }
+    }
+}
+
+/// Adds the flags read by [`ParseOptions::from_call`] to a command signature.
+pub trait ParseFlags {
+    fn parse_flags(self) -> Self;
+}
+
+impl ParseFlags for Signature {
+    fn parse_flags(self) -> Self {
+        self.named(
+            "parse-json",
+            SyntaxShape::List(Box::new(SyntaxShape::String)),
+            "Parse these string columns as JSON, or any JSON object or array when empty",
+            None,
+        )
+        .switch(
+            "parse-xml",
+            "Parse XML columns and FOR XML results into the records produced by from xml",
+            None,
+        )
+        .named(
+            "spatial",
+            SyntaxShape::String,
+            "Return geometry and geography columns as wkt (default) or geojson",
+            None,
+        )
+        .named(
+            "guid",
+            SyntaxShape::String,
+            "Write uniqueidentifier values as lower (default), upper or braced",
+            None,
+        )
+        .named(
+            "binary",
+            SyntaxShape::String,
+            "Return binary values as binary (default), hex or base64",
+            None,
+        )
+        .switch(
+            "trim-char",
+            "Trim the trailing spaces that pad char and nchar values",
+            None,
+        )
+        .named(
+            "time",
+            SyntaxShape::String,
+            "Return time values as a duration since midnight (default) or an HH:MM:SS.fffffff string",
+            None,
+        )
+        .named(
+            "combine-datetime",
+            SyntaxShape::Record(vec![]),
+            "Combine date columns with time columns into one datetime, e.g. {OrderDate: OrderTime}",
+            None,
+        )
+        .named(
+            "duplicate-columns",
+            SyntaxShape::String,
+            "Name repeated columns Id_1 with rename (default), Users.Id with prefix-table, or error",
+            None,
+        )
+    }
+}
+
+/// The format of a result set split over rows by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chunked {
+    Json,
+    Xml,
+}
+
+/// Turns the rows of each result set into values.
+///
+/// SQL Server splits the text of a `FOR JSON` or `FOR XML` result over several
+/// rows of a single column, so those rows are collected until the result set
+/// ends and emitted as the parsed document. JSON arrays produce one value per
+/// element, and XML one value per top-level node. `FOR XML` results are only
+/// reassembled with `--parse-xml`.
+#[derive(Debug, Default)]
+pub struct ResultReader {
+    options: ParseOptions,
+    chunked: Option<(Chunked, String)>,
+    names: Vec<String>,
+    result_sets: usize,
+}
+
+impl ResultReader {
+    pub fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            chunked: None,
+            names: vec![],
+            result_sets: 0,
+        }
+    }
+
+    /// The field names of the current result set.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Starts a new result set with the given columns.
+    #[allow(clippy::result_large_err)]
+    pub fn metadata(&mut self, columns: &[Column]) -> Result<Vec<Value>, LabeledError> {
+        let values = self.finish()?;
+        self.result_sets += 1;
+        // Source tables and wrapped types are only known for the first result set.
+        if self.result_sets == 2 {
+            self.options.wrapped.clear();
+        }
+        let tables = match self.result_sets {
+            1 => self.options.source_tables.as_slice(),
+            _ => &[],
+        };
+        self.names = column_names(columns, self.options.duplicate_columns, tables)?;
+        self.chunked = match columns {
+            [column] if column.name() == FOR_JSON_COLUMN => Some((Chunked::Json, String::new())),
+            [column] if column.name() == FOR_XML_COLUMN && self.options.parse_xml => {
+                Some((Chunked::Xml, String::new()))
+            }
+            _ => None,
+        };
+        Ok(values)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn row(&mut self, row: &Row) -> Result<Vec<Value>, LabeledError> {
+        match &mut self.chunked {
+            Some((_, text)) => {
+                match row.cells().next() {
+                    Some((_, ColumnData::String(Some(chunk)))) => text.push_str(chunk),
+                    Some((_, ColumnData::Xml(Some(chunk)))) => text.push_str(&chunk.to_string()),
+                    _ => {}
+                }
+                Ok(vec![])
+            }
+            None => Ok(vec![self.options.parse_row(row, &self.names)?]),
+        }
+    }
+
+    /// Ends the current result set, returning any values held back.
+    #[allow(clippy::result_large_err)]
+    pub fn finish(&mut self) -> Result<Vec<Value>, LabeledError> {
+        let Some((format, text)) = self.chunked.take() else {
+            return Ok(vec![]);
+        };
+        if text.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let value = match format {
+            Chunked::Json => parse_json(&text)
+                .map_err(|e| LabeledError::new(format!("Error parsing FOR JSON result: {e}")))?,
+            Chunked::Xml => parse_xml(&text)
+                .map_err(|e| LabeledError::new(format!("Error parsing FOR XML result: {e}")))?,
+        };
+        match value {
+            Value::List { vals, .. } => Ok(vals),
+            value => Ok(vec![value]),
+        }
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str(text).map(|json| json_to_value(json, Span::unknown()))
+}
+
+fn json_to_value(json: serde_json::Value, span: Span) -> Value {
+    match json {
+        serde_json::Value::Null => Value::nothing(span),
+        serde_json::Value::Bool(value) => Value::bool(value, span),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => Value::int(value, span),
+            None => Value::float(number.as_f64().unwrap_or(f64::NAN), span),
+        },
+        serde_json::Value::String(value) => Value::string(value, span),
+        serde_json::Value::Array(values) => Value::list(
+            values
+                .into_iter()
+                .map(|value| json_to_value(value, span))
+                .collect(),
+            span,
+        ),
+        serde_json::Value::Object(map) => Value::record(
+            map.into_iter()
+                .map(|(key, value)| (key, json_to_value(value, span)))
+                .collect(),
+            span,
+        ),
+    }
+}
+
+#[test]
+fn test_column_names() {
+    let columns =
+        ["Id", "", "Id", "Id_1", ""].map(|name| Column::new(name.to_string(), ColumnType::Int4));
+    assert_eq!(
+        column_names(&columns, DuplicateColumns::Rename, &[]).unwrap(),
+        vec!["Id", "column2", "Id_1", "Id_1_1", "column5"]
+    );
+    assert!(column_names(&columns, DuplicateColumns::Error, &[]).is_err());
+
+    let tables = [Some("Users"), None, Some("Orders"), None, None].map(|t| t.map(String::from));
+    assert_eq!(
+        column_names(&columns, DuplicateColumns::PrefixTable, &tables).unwrap(),
+        vec!["Users.Id", "column2", "Orders.Id", "Id_1", "column5"]
+    );
+}
+
+#[test]
+fn test_output_formats() {
+    let guid = tiberius::Uuid::from_u128(0x6f9619ff_8b86_d011_b42d_00c04fc964ff);
+    assert_eq!(
+        GuidFormat::Upper.format(&guid),
+        "6F9619FF-8B86-D011-B42D-00C04FC964FF"
+    );
+    assert_eq!(
+        GuidFormat::Braced.format(&guid),
+        "{6f9619ff-8b86-d011-b42d-00c04fc964ff}"
+    );
+
+    let span = Span::unknown();
+    assert_eq!(
+        BinaryFormat::Hex.format(vec![0x0a, 0xff], span),
+        Value::string("0aff", span)
+    );
+    assert_eq!(
+        BinaryFormat::Base64.format(b"nu".to_vec(), span),
+        Value::string("bnU=", span)
+    );
+}
+
+#[test]
+fn test_parse_json() {
+    let value = parse_json(r#"[{"b": 1, "a": [true, null, 2.5]}]"#).unwrap();
+    let rows = value.as_list().unwrap();
+    let record = rows[0].as_record().unwrap();
+    assert_eq!(record.columns().collect::<Vec<_>>(), vec!["b", "a"]);
+    assert_eq!(record.get("b"), Some(&Value::int(1, Span::unknown())));
+    assert_eq!(record.get("a").unwrap().as_list().unwrap().len(), 3);
+}
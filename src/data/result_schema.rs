@@ -0,0 +1,274 @@
+use async_std::{net::TcpStream, stream::StreamExt};
+use nu_protocol::{record, LabeledError, Record, Span, Value};
+use tiberius::{Client, Column, ColumnType, QueryItem};
+
+use super::{query_records, unwrap_types, DuplicateColumns, ParseOptions, ResultReader};
+
+/// A column of a query result.
+///
+/// The TDS metadata exposed by tiberius only carries the name and type, so
+/// nullability, precision, scale and length are read from
+/// `sys.dm_exec_describe_first_result_set` and left empty when the server
+/// cannot describe the batch, e.g. when it uses temporary tables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultColumn {
+    pub name: String,
+    pub sql_type: String,
+    pub nullable: Option<bool>,
+    pub precision: Option<i64>,
+    pub scale: Option<i64>,
+    /// The declared length, in characters for character types, `-1` for `max`.
+    pub length: Option<i64>,
+}
+
+impl ResultColumn {
+    pub fn from_column(column: &Column) -> Self {
+        Self {
+            name: column.name().to_string(),
+            sql_type: column_type_name(column.column_type()).to_string(),
+            nullable: None,
+            precision: None,
+            scale: None,
+            length: None,
+        }
+    }
+
+    pub fn into_record(self, span: Span) -> Record {
+        let optional = |value: Option<Value>| value.unwrap_or(Value::nothing(span));
+        record! {
+            "name" => Value::string(self.name, span),
+            "sql_type" => Value::string(self.sql_type, span),
+            "nullable" => optional(self.nullable.map(|nullable| Value::bool(nullable, span))),
+            "precision" => optional(self.precision.map(|precision| Value::int(precision, span))),
+            "scale" => optional(self.scale.map(|scale| Value::int(scale, span))),
+            "length" => optional(self.length.map(|length| Value::int(length, span))),
+        }
+    }
+}
+
+/// The name of the SQL Server type a TDS column type is sent as.
+///
+/// Nullable integers, floats and datetimes share a variable length TDS type,
+/// so their exact size is only known from the described result set.
+pub fn column_type_name(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Null => "null",
+        ColumnType::Bit | ColumnType::Bitn => "bit",
+        ColumnType::Int1 => "tinyint",
+        ColumnType::Int2 => "smallint",
+        ColumnType::Int4 | ColumnType::Intn => "int",
+        ColumnType::Int8 => "bigint",
+        ColumnType::Datetime4 => "smalldatetime",
+        ColumnType::Float4 => "real",
+        ColumnType::Float8 | ColumnType::Floatn => "float",
+        ColumnType::Money => "money",
+        ColumnType::Money4 => "smallmoney",
+        ColumnType::Datetime | ColumnType::Datetimen => "datetime",
+        ColumnType::Guid => "uniqueidentifier",
+        ColumnType::Decimaln => "decimal",
+        ColumnType::Numericn => "numeric",
+        ColumnType::Daten => "date",
+        ColumnType::Timen => "time",
+        ColumnType::Datetime2 => "datetime2",
+        ColumnType::DatetimeOffsetn => "datetimeoffset",
+        ColumnType::BigVarBin => "varbinary",
+        ColumnType::BigVarChar => "varchar",
+        ColumnType::BigBinary => "binary",
+        ColumnType::BigChar => "char",
+        ColumnType::NVarchar => "nvarchar",
+        ColumnType::NChar => "nchar",
+        ColumnType::Xml => "xml",
+        ColumnType::Udt => "udt",
+        ColumnType::Text => "text",
+        ColumnType::Image => "image",
+        ColumnType::NText => "ntext",
+        ColumnType::SSVariant => "sql_variant",
+    }
+}
+
+/// Describes the first result set of `sql` without running it, or returns
+/// nothing if the server cannot.
+pub(crate) async fn describe_result(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+) -> Option<Vec<ResultColumn>> {
+    let rows = query_records(
+        client,
+        "SELECT name, system_type_name, is_nullable, precision, scale, max_length,
+            CASE WHEN system_type_name LIKE 'n%char%' AND max_length > 0
+                THEN max_length / 2 ELSE max_length END AS length
+        FROM sys.dm_exec_describe_first_result_set(@P1, NULL, 0)
+        WHERE is_hidden = 0
+        ORDER BY column_ordinal",
+        &[&sql],
+    )
+    .await
+    .ok()?;
+
+    let optional_int = |row: &Value, name: &str| {
+        row.get_data_by_key(name)
+            .and_then(|value| value.as_int().ok())
+    };
+    Some(
+        rows.iter()
+            .map(|row| ResultColumn {
+                name: row
+                    .get_data_by_key("name")
+                    .and_then(|value| value.as_str().ok().map(str::to_string))
+                    .unwrap_or_default(),
+                sql_type: row
+                    .get_data_by_key("system_type_name")
+                    .and_then(|value| value.as_str().ok().map(str::to_string))
+                    .unwrap_or_default(),
+                nullable: row
+                    .get_data_by_key("is_nullable")
+                    .and_then(|value| value.as_bool().ok()),
+                precision: optional_int(row, "precision"),
+                scale: optional_int(row, "scale"),
+                length: optional_int(row, "length"),
+            })
+            .collect(),
+    )
+}
+
+/// Reads what `options` needs to know about the columns of `sql` from the
+/// server, returning the query to run. `described` is the
+/// [`describe_result`] of `sql`.
+pub async fn prepare_query(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+    described: Option<&[ResultColumn]>,
+    options: &mut ParseOptions,
+) -> Result<String, LabeledError> {
+    if options.duplicate_columns == DuplicateColumns::PrefixTable {
+        options.source_tables = source_tables(client, sql).await;
+    }
+    let (query, wrapped) = unwrap_types(client, sql, described).await?;
+    options.wrapped = wrapped;
+    Ok(query)
+}
+
+/// The table each column of the first result set of `sql` is read from, or
+/// nothing for expressions and batches the server cannot describe.
+async fn source_tables(client: &mut Client<TcpStream>, sql: &str) -> Vec<Option<String>> {
+    let rows = query_records(
+        client,
+        "SELECT source_table
+        FROM sys.dm_exec_describe_first_result_set(@P1, NULL, 1)
+        WHERE is_hidden = 0
+        ORDER BY column_ordinal",
+        &[&sql],
+    )
+    .await
+    .unwrap_or_default();
+
+    rows.iter()
+        .map(|row| {
+            row.get_data_by_key("source_table")
+                .and_then(|value| value.as_str().ok().map(str::to_string))
+        })
+        .collect()
+}
+
+/// Runs `sql` and returns its first result set as `{columns, rows}`, so the
+/// columns are reported even when there are no rows. Each column also
+/// carries the `duplicate_columns` policy its name was chosen under.
+pub async fn query_with_schema(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+    mut options: ParseOptions,
+) -> Result<Value, LabeledError> {
+    let described = describe_result(client, sql).await;
+    let sql = prepare_query(client, sql, described.as_deref(), &mut options).await?;
+    let duplicate_columns = options.duplicate_columns;
+
+    let mut stream = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error running query: {e}")))?;
+
+    let mut reader = ResultReader::new(options);
+    let mut columns = vec![];
+    let mut rows = vec![];
+    let mut result_sets = 0;
+    while let Some(item) = stream.next().await {
+        match item.map_err(|e| LabeledError::new(format!("Error reading results: {e}")))? {
+            QueryItem::Metadata(metadata) => {
+                result_sets += 1;
+                if result_sets == 1 {
+                    reader.metadata(metadata.columns())?;
+                    columns =
+                        result_columns(metadata.columns(), reader.names(), described.as_deref());
+                } else if result_sets == 2 {
+                    rows.extend(reader.finish()?);
+                }
+            }
+            // Later result sets are drained to leave the connection usable.
+            QueryItem::Row(row) if result_sets == 1 => rows.extend(reader.row(&row)?),
+            QueryItem::Row(_) => {}
+        }
+    }
+    rows.extend(reader.finish()?);
+
+    Ok(schema_value(
+        columns,
+        rows,
+        duplicate_columns,
+        Span::unknown(),
+    ))
+}
+
+/// The columns of a result set under the field `names` they are read into,
+/// with the details of `described` when it has the same number of columns.
+pub(crate) fn result_columns(
+    columns: &[Column],
+    names: &[String],
+    described: Option<&[ResultColumn]>,
+) -> Vec<ResultColumn> {
+    let mut columns: Vec<ResultColumn> = columns
+        .iter()
+        .zip(names)
+        .map(|(column, name)| ResultColumn {
+            name: name.clone(),
+            ..ResultColumn::from_column(column)
+        })
+        .collect();
+    if let Some(described) = described.filter(|described| described.len() == columns.len()) {
+        for (column, described) in columns.iter_mut().zip(described) {
+            column.sql_type = described.sql_type.clone();
+            column.nullable = described.nullable;
+            column.precision = described.precision;
+            column.scale = described.scale;
+            column.length = described.length;
+        }
+    }
+    columns
+}
+
+/// A result set as `{columns, rows}`, with the `duplicate_columns` policy
+/// added to each column.
+pub(crate) fn schema_value(
+    columns: Vec<ResultColumn>,
+    rows: Vec<Value>,
+    duplicate_columns: DuplicateColumns,
+    span: Span,
+) -> Value {
+    Value::record(
+        record! {
+            "columns" => Value::list(
+                columns
+                    .into_iter()
+                    .map(|column| {
+                        let mut record = column.into_record(span);
+                        let policy = Value::string(duplicate_columns.name(), span);
+                        record.push("duplicate_columns", policy);
+                        Value::record(record, span)
+                    })
+                    .collect(),
+                span,
+            ),
+            "rows" => Value::list(rows, span),
+        },
+        span,
+    )
+}
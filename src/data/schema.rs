@@ -0,0 +1,230 @@
+use async_std::{net::TcpStream, stream::StreamExt};
+use nu_protocol::LabeledError;
+use tiberius::{Client, Query};
+
+/// A column of a table as described by the `sys.columns` catalog view.
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+    pub name: String,
+    pub sql_type: String,
+    pub max_length: i16,
+    pub precision: u8,
+    pub scale: u8,
+    pub nullable: bool,
+    pub identity: bool,
+    pub computed: bool,
+}
+
+impl TableColumn {
+    /// Whether SQL Server accepts values for this column in a bulk load.
+    pub fn is_writable(&self) -> bool {
+        !self.identity && !self.computed && self.sql_type != "timestamp"
+    }
+
+    /// The type as it would be written in a declaration, e.g. `nvarchar(50)` or `decimal(10, 2)`.
+    pub fn type_declaration(&self) -> String {
+        type_declaration(&self.sql_type, self.max_length, self.precision, self.scale)
+    }
+}
+
+/// Formats a catalog type with its length, precision or scale as used in declarations.
+///
+/// `max_length` is in bytes as reported by the catalog views, `-1` meaning `max`.
+pub fn type_declaration(sql_type: &str, max_length: i16, precision: u8, scale: u8) -> String {
+    let length = |bytes: i16| match bytes {
+        -1 => "max".to_string(),
+        bytes => bytes.to_string(),
+    };
+
+    match sql_type {
+        "char" | "varchar" | "binary" | "varbinary" => {
+            format!("{sql_type}({})", length(max_length))
+        }
+        "nchar" | "nvarchar" => format!(
+            "{sql_type}({})",
+            length(if max_length > 0 {
+                max_length / 2
+            } else {
+                max_length
+            })
+        ),
+        "decimal" | "numeric" => format!("{sql_type}({precision}, {scale})"),
+        "time" | "datetime2" | "datetimeoffset" => format!("{sql_type}({scale})"),
+        other => other.to_string(),
+    }
+}
+
+/// Reads the column definitions of `table`, in column order.
+///
+/// Temporary tables (`#name`) are looked up in `tempdb`.
+pub async fn table_columns(
+    client: &mut Client<TcpStream>,
+    table: &str,
+) -> Result<Vec<TableColumn>, LabeledError> {
+    let (catalog, object) = if table.starts_with('#') {
+        ("tempdb.", format!("tempdb..{table}"))
+    } else {
+        ("", table.to_string())
+    };
+
+    let sql = format!(
+        "SELECT c.name,
+            CASE WHEN t.is_user_defined = 1 AND t.is_assembly_type = 0
+                THEN TYPE_NAME(c.system_type_id) ELSE t.name END AS type_name,
+            c.max_length, c.precision, c.scale, c.is_nullable, c.is_identity, c.is_computed
+        FROM {catalog}sys.columns c
+        JOIN {catalog}sys.types t ON t.user_type_id = c.user_type_id
+        WHERE c.object_id = OBJECT_ID(@P1)
+        ORDER BY c.column_id"
+    );
+
+    let mut query = Query::new(sql);
+    query.bind(object);
+
+    let mut stream = query
+        .query(client)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error reading columns of {table}: {e}")))?
+        .into_row_stream();
+
+    let mut columns = vec![];
+    while let Some(row) = stream.next().await {
+        let row = row.map_err(|e| LabeledError::new(format!("Error reading columns: {e}")))?;
+        columns.push(TableColumn {
+            name: row.get::<&str, _>(0).unwrap_or_default().to_string(),
+            sql_type: match row.get::<&str, _>(1).unwrap_or_default() {
+                "sysname" => "nvarchar".to_string(),
+                other => other.to_string(),
+            },
+            max_length: row.get(2).unwrap_or_default(),
+            precision: row.get(3).unwrap_or_default(),
+            scale: row.get(4).unwrap_or_default(),
+            nullable: row.get(5).unwrap_or_default(),
+            identity: row.get(6).unwrap_or_default(),
+            computed: row.get(7).unwrap_or_default(),
+        });
+    }
+
+    if columns.is_empty() {
+        return Err(LabeledError::new(format!(
+            "Table {table} does not exist or has no columns"
+        )));
+    }
+
+    Ok(columns)
+}
+
+/// Quotes an identifier with brackets so it can be embedded in generated SQL.
+pub fn quote_identifier(name: &str) -> String {
+    format!("[{}]", name.replace(']', "]]"))
+}
+
+/// Quotes a possibly schema qualified name as the user wrote it, e.g.
+/// `dbo.Users` becomes `[dbo].[Users]`.
+///
+/// Parts already in brackets or double quotes are taken as they are, so
+/// `dbo.[Order.Details]` is only split at its first dot.
+pub fn quote_name(name: &str) -> String {
+    let mut parts = vec![String::new()];
+    let mut chars = name.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => parts.push(String::new()),
+            '[' | '"' => {
+                let close = if c == '[' { ']' } else { '"' };
+                let part = parts.last_mut().expect("one part");
+                while let Some(c) = chars.next() {
+                    if c == close {
+                        if chars.peek() != Some(&close) {
+                            break;
+                        }
+                        chars.next();
+                    }
+                    part.push(c);
+                }
+            }
+            c => parts.last_mut().expect("one part").push(c),
+        }
+    }
+
+    parts
+        .iter()
+        .map(|part| quote_identifier(part))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Finds where `keyword` occurs in `sql` outside parentheses, quoted text and
+/// comments, e.g. the `ORDER BY` of a query rather than of its subqueries.
+pub(crate) fn keyword_positions(sql: &str, keyword: &str) -> Vec<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '@' || c == '#';
+    let mut positions = vec![];
+    let mut depth = 0;
+    let mut previous = ' ';
+    let mut chars = sql.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '\'' | '"' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                while let Some((_, c)) = chars.next() {
+                    if c == close {
+                        if chars.peek().map(|(_, next)| *next) != Some(close) {
+                            break;
+                        }
+                        chars.next();
+                    }
+                }
+            }
+            '-' if next == Some('-') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if next == Some('*') => {
+                chars.next();
+                let mut star = false;
+                for (_, c) in chars.by_ref() {
+                    if star && c == '/' {
+                        break;
+                    }
+                    star = c == '*';
+                }
+            }
+            _ if depth == 0
+                && !is_word(previous)
+                && sql[i..]
+                    .get(..keyword.len())
+                    .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+                && !sql[i + keyword.len()..].starts_with(is_word) =>
+            {
+                positions.push(i);
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    positions
+}
+
+#[test]
+fn test_keyword_positions() {
+    let sql = "SELECT [order] FROM t -- order\nWHERE x IN (SELECT 1 ORDER BY 1) /* ORDER */ ORDER BY 'order', reorder";
+    assert_eq!(
+        keyword_positions(sql, "order"),
+        vec![sql.rfind("ORDER BY 'o").unwrap()]
+    );
+    assert_eq!(keyword_positions(sql, "SELECT"), vec![0]);
+}
+
+#[test]
+fn test_quote_name() {
+    assert_eq!(quote_name("dbo.Users"), "[dbo].[Users]");
+    assert_eq!(quote_name("Users"), "[Users]");
+    assert_eq!(quote_name("dbo.[Order.Details]"), "[dbo].[Order.Details]");
+    assert_eq!(quote_name("[a]]b].\"c\"\"d\""), "[a]]b].[c\"d]");
+}
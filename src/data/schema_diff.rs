@@ -0,0 +1,723 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_std::net::TcpStream;
+use nu_protocol::{record, LabeledError, Span, Value};
+use tiberius::Client;
+
+use super::{
+    field, flag, group_by_name, int, object_kind, query_records, quote_identifier, script_object,
+    strings, text, type_declaration,
+};
+
+/// The kinds of objects compared, in the order they are created by a migration.
+const KINDS: [&str; 11] = [
+    "table",
+    "column",
+    "default",
+    "identity",
+    "constraint",
+    "foreign key",
+    "index",
+    "view",
+    "function",
+    "procedure",
+    "trigger",
+];
+
+/// An object of a database schema with the statements to create and drop it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaItem {
+    pub kind: &'static str,
+    /// The schema qualified name, e.g. `dbo.Users.Email` for a column.
+    pub name: String,
+    /// The table a column, index, constraint or trigger belongs to, or the
+    /// column of a default or identity.
+    pub parent: Option<String>,
+    /// The text compared between the two databases.
+    pub definition: String,
+    /// `None` for tables, which are scripted from the source when needed.
+    pub create: Option<String>,
+    pub drop: String,
+    /// A statement changing the object in place, where one exists.
+    pub alter: Option<String>,
+}
+
+/// The objects of a database, keyed by kind and name.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaSnapshot {
+    items: BTreeMap<(usize, String), SchemaItem>,
+}
+
+impl SchemaSnapshot {
+    pub fn insert(&mut self, item: SchemaItem) {
+        let order = KINDS
+            .iter()
+            .position(|kind| *kind == item.kind)
+            .unwrap_or(KINDS.len());
+        self.items.insert((order, item.name.clone()), item);
+    }
+
+    fn get(&self, key: &(usize, String)) -> Option<&SchemaItem> {
+        self.items.get(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+}
+
+/// A difference between the source and target schemas.
+///
+/// `Added` objects exist only in the source, `Removed` objects only in the
+/// target. `before` is the target's definition and `after` the source's, so
+/// applying the differences brings the target in line with the source.
+#[derive(Debug, Clone)]
+pub struct SchemaDifference {
+    pub kind: &'static str,
+    pub name: String,
+    pub change: Change,
+    pub before: Option<SchemaItem>,
+    pub after: Option<SchemaItem>,
+}
+
+impl SchemaDifference {
+    pub fn into_value(self, span: Span) -> Value {
+        let definition = |item: Option<SchemaItem>| match item {
+            Some(item) => Value::string(item.definition, span),
+            None => Value::nothing(span),
+        };
+        Value::record(
+            record! {
+                "kind" => Value::string(self.kind, span),
+                "name" => Value::string(self.name, span),
+                "change" => Value::string(self.change.as_str(), span),
+                "before" => definition(self.before),
+                "after" => definition(self.after),
+            },
+            span,
+        )
+    }
+}
+
+/// Reads the tables, columns, constraints, indexes and programmable objects of
+/// the connected database.
+pub async fn schema_snapshot(
+    client: &mut Client<TcpStream>,
+) -> Result<SchemaSnapshot, LabeledError> {
+    let mut snapshot = SchemaSnapshot::default();
+
+    let tables = query_records(
+        client,
+        "SELECT s.name + '.' + t.name AS name, QUOTENAME(s.name) + '.' + QUOTENAME(t.name) AS quoted
+        FROM sys.tables t
+        JOIN sys.schemas s ON s.schema_id = t.schema_id
+        WHERE t.is_ms_shipped = 0",
+        &[],
+    )
+    .await?;
+    for table in tables {
+        let name = text(&table, "name");
+        snapshot.insert(SchemaItem {
+            kind: "table",
+            drop: format!("DROP TABLE {};", text(&table, "quoted")),
+            name,
+            parent: None,
+            definition: String::new(),
+            create: None,
+            alter: None,
+        });
+    }
+
+    let columns = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS [table], QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted_table, c.name,
+            CASE WHEN t.is_user_defined = 1 AND t.is_assembly_type = 0
+                THEN TYPE_NAME(c.system_type_id) ELSE t.name END AS type_name,
+            c.max_length, c.precision, c.scale, c.is_nullable AS nullable,
+            c.collation_name AS collation, cc.definition AS computed, d.definition AS [default],
+            d.name AS default_name, d.is_system_named AS default_system_named,
+            CAST(ic.seed_value AS BIGINT) AS seed, CAST(ic.increment_value AS BIGINT) AS increment
+        FROM sys.columns c
+        JOIN sys.tables o ON o.object_id = c.object_id
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        JOIN sys.types t ON t.user_type_id = c.user_type_id
+        LEFT JOIN sys.default_constraints d ON d.object_id = c.default_object_id
+        LEFT JOIN sys.computed_columns cc
+            ON cc.object_id = c.object_id AND cc.column_id = c.column_id
+        LEFT JOIN sys.identity_columns ic
+            ON ic.object_id = c.object_id AND ic.column_id = c.column_id
+        WHERE o.is_ms_shipped = 0",
+        &[],
+    )
+    .await?;
+    for column in columns {
+        for item in column_items(&column) {
+            snapshot.insert(item);
+        }
+    }
+
+    let keys = group_by_name(
+        query_records(
+            client,
+            "SELECT s.name + '.' + o.name AS [table], s.name + '.' + o.name + '.' + k.name AS name,
+                QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted_table,
+                k.name AS [constraint],
+                CASE k.type WHEN 'PK' THEN 'PRIMARY KEY ' ELSE 'UNIQUE ' END + i.type_desc AS type,
+                QUOTENAME(c.name) + CASE WHEN ic.is_descending_key = 1 THEN ' DESC' ELSE '' END
+                    AS columns
+            FROM sys.key_constraints k
+            JOIN sys.tables o ON o.object_id = k.parent_object_id
+            JOIN sys.schemas s ON s.schema_id = o.schema_id
+            JOIN sys.indexes i ON i.object_id = k.parent_object_id AND i.index_id = k.unique_index_id
+            JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+            JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id
+            WHERE o.is_ms_shipped = 0
+            ORDER BY 2, ic.key_ordinal",
+            &[],
+        )
+        .await?,
+        &["columns"],
+    );
+    for key in keys {
+        let definition = format!("{} ({})", text(&key, "type"), joined(&key, "columns"));
+        snapshot.insert(constraint_item(&key, "constraint", definition));
+    }
+
+    let checks = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS [table], s.name + '.' + o.name + '.' + cc.name AS name,
+                QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted_table,
+            cc.name AS [constraint], cc.definition
+        FROM sys.check_constraints cc
+        JOIN sys.tables o ON o.object_id = cc.parent_object_id
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.is_ms_shipped = 0",
+        &[],
+    )
+    .await?;
+    for check in checks {
+        let definition = format!("CHECK {}", text(&check, "definition"));
+        snapshot.insert(constraint_item(&check, "constraint", definition));
+    }
+
+    let foreign_keys = group_by_name(
+        query_records(
+            client,
+            "SELECT s.name + '.' + o.name AS [table], s.name + '.' + o.name + '.' + fk.name AS name,
+                QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted_table,
+                fk.name AS [constraint],
+                QUOTENAME(OBJECT_SCHEMA_NAME(fk.referenced_object_id)) + '.'
+                    + QUOTENAME(OBJECT_NAME(fk.referenced_object_id)) AS [references],
+                fk.delete_referential_action_desc AS on_delete,
+                fk.update_referential_action_desc AS on_update,
+                QUOTENAME(c.name) AS columns, QUOTENAME(rc.name) AS referenced_columns
+            FROM sys.foreign_keys fk
+            JOIN sys.tables o ON o.object_id = fk.parent_object_id
+            JOIN sys.schemas s ON s.schema_id = o.schema_id
+            JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+            JOIN sys.columns c
+                ON c.object_id = fkc.parent_object_id AND c.column_id = fkc.parent_column_id
+            JOIN sys.columns rc
+                ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id
+            WHERE o.is_ms_shipped = 0
+            ORDER BY 2, fkc.constraint_column_id",
+            &[],
+        )
+        .await?,
+        &["columns", "referenced_columns"],
+    );
+    for foreign_key in foreign_keys {
+        let mut definition = format!(
+            "FOREIGN KEY ({}) REFERENCES {} ({})",
+            joined(&foreign_key, "columns"),
+            text(&foreign_key, "references"),
+            joined(&foreign_key, "referenced_columns")
+        );
+        for (clause, field) in [("ON DELETE", "on_delete"), ("ON UPDATE", "on_update")] {
+            let action = text(&foreign_key, field);
+            if action != "NO_ACTION" {
+                definition.push_str(&format!(" {clause} {}", action.replace('_', " ")));
+            }
+        }
+        snapshot.insert(constraint_item(&foreign_key, "foreign key", definition));
+    }
+
+    let indexes = group_by_name(
+        query_records(
+            client,
+            "SELECT s.name + '.' + o.name AS [table], s.name + '.' + o.name + '.' + i.name AS name,
+                QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted_table,
+                i.name AS [index], i.type_desc AS type, i.is_unique AS [unique],
+                i.filter_definition AS filter,
+                CASE WHEN ic.is_included_column = 0 THEN QUOTENAME(c.name)
+                    + CASE WHEN ic.is_descending_key = 1 THEN ' DESC' ELSE '' END END AS columns,
+                CASE WHEN ic.is_included_column = 1 THEN QUOTENAME(c.name) END AS included
+            FROM sys.indexes i
+            JOIN sys.tables o ON o.object_id = i.object_id
+            JOIN sys.schemas s ON s.schema_id = o.schema_id
+            JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id
+            JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id
+            WHERE o.is_ms_shipped = 0 AND i.type IN (1, 2)
+                AND i.is_primary_key = 0 AND i.is_unique_constraint = 0
+            ORDER BY 2, ic.is_included_column, ic.key_ordinal, ic.index_column_id",
+            &[],
+        )
+        .await?,
+        &["columns", "included"],
+    );
+    for index in indexes {
+        snapshot.insert(index_item(&index));
+    }
+
+    let modules = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS name, QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted,
+            o.type, m.definition,
+            OBJECT_SCHEMA_NAME(o.parent_object_id) + '.' + OBJECT_NAME(o.parent_object_id) AS parent
+        FROM sys.sql_modules m
+        JOIN sys.objects o ON o.object_id = m.object_id
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.is_ms_shipped = 0 AND o.type IN ('V', 'P', 'FN', 'IF', 'TF', 'TR')",
+        &[],
+    )
+    .await?;
+    for module in modules {
+        let Some(kind) = object_kind(&text(&module, "type")) else {
+            continue;
+        };
+        let name = text(&module, "name");
+        let definition = text(&module, "definition").trim().replace("\r\n", "\n");
+        let parent = text(&module, "parent");
+        snapshot.insert(SchemaItem {
+            kind,
+            drop: format!(
+                "DROP {} IF EXISTS {};",
+                kind.to_uppercase(),
+                text(&module, "quoted")
+            ),
+            create: Some(definition.clone()),
+            name,
+            parent: (!parent.is_empty()).then_some(parent),
+            definition,
+            alter: None,
+        });
+    }
+
+    Ok(snapshot)
+}
+
+/// Builds the item of a column, followed by items for its default and
+/// identity. These are compared on their own, so that a default can be
+/// replaced without altering the column.
+fn column_items(column: &Value) -> Vec<SchemaItem> {
+    let table = text(column, "table");
+    let name = text(column, "name");
+    let column_name = format!("{table}.{name}");
+    let quoted_table = text(column, "quoted_table");
+    let quoted = quote_identifier(&name);
+
+    let computed = text(column, "computed");
+    if !computed.is_empty() {
+        return vec![SchemaItem {
+            kind: "column",
+            name: column_name,
+            parent: Some(table),
+            create: Some(format!(
+                "ALTER TABLE {quoted_table} ADD {quoted} AS {computed};"
+            )),
+            drop: format!("ALTER TABLE {quoted_table} DROP COLUMN {quoted};"),
+            definition: format!("AS {computed}"),
+            alter: None,
+        }];
+    }
+
+    let mut definition = type_declaration(
+        &text(column, "type_name"),
+        int(column, "max_length") as i16,
+        int(column, "precision") as u8,
+        int(column, "scale") as u8,
+    );
+    let collation = text(column, "collation");
+    if !collation.is_empty() {
+        definition.push_str(&format!(" COLLATE {collation}"));
+    }
+    let nullability = match flag(column, "nullable") {
+        true => " NULL",
+        false => " NOT NULL",
+    };
+
+    let identity = (!field(column, "seed").is_nothing()).then(|| {
+        format!(
+            "IDENTITY({}, {})",
+            int(column, "seed"),
+            int(column, "increment")
+        )
+    });
+    let default = text(column, "default");
+    // System named defaults get a new generated name in the target.
+    let constraint = match flag(column, "default_system_named") {
+        true => String::new(),
+        false => format!(
+            "CONSTRAINT {} ",
+            quote_identifier(&text(column, "default_name"))
+        ),
+    };
+
+    let mut create = format!("ALTER TABLE {quoted_table} ADD {quoted} {definition}");
+    if let Some(identity) = &identity {
+        create.push_str(&format!(" {identity}"));
+    }
+    create.push_str(nullability);
+    let mut drop = format!("ALTER TABLE {quoted_table} DROP COLUMN {quoted};");
+    if !default.is_empty() {
+        create.push_str(&format!(" {constraint}DEFAULT {default}"));
+        drop = format!(
+            "ALTER TABLE {quoted_table} DROP CONSTRAINT {};\n{drop}",
+            quote_identifier(&text(column, "default_name"))
+        );
+    }
+
+    let mut items = vec![SchemaItem {
+        kind: "column",
+        name: column_name.clone(),
+        parent: Some(table),
+        create: Some(format!("{create};")),
+        drop,
+        alter: Some(format!(
+            "ALTER TABLE {quoted_table} ALTER COLUMN {quoted} {definition}{nullability};"
+        )),
+        definition: format!("{definition}{nullability}"),
+    }];
+
+    if !default.is_empty() {
+        items.push(SchemaItem {
+            kind: "default",
+            name: column_name.clone(),
+            parent: Some(column_name.clone()),
+            create: Some(format!(
+                "ALTER TABLE {quoted_table} ADD {constraint}DEFAULT {default} FOR {quoted};"
+            )),
+            drop: format!(
+                "ALTER TABLE {quoted_table} DROP CONSTRAINT {};",
+                quote_identifier(&text(column, "default_name"))
+            ),
+            definition: format!("DEFAULT {default}"),
+            alter: None,
+        });
+    }
+
+    // SQL Server cannot add, remove or change the identity of an existing
+    // column, so the migration only flags it.
+    if let Some(identity) = identity {
+        items.push(SchemaItem {
+            kind: "identity",
+            name: column_name.clone(),
+            parent: Some(column_name.clone()),
+            create: Some(format!(
+                "-- {identity} cannot be added to the existing column {column_name}, \
+                the table needs to be rebuilt"
+            )),
+            drop: format!(
+                "-- The identity cannot be removed from {column_name}, the table needs to be rebuilt"
+            ),
+            alter: Some(format!(
+                "-- The identity of {column_name} cannot be changed to {identity}, \
+                the table needs to be rebuilt"
+            )),
+            definition: identity,
+        });
+    }
+
+    items
+}
+
+fn constraint_item(row: &Value, kind: &'static str, definition: String) -> SchemaItem {
+    let table = text(row, "quoted_table");
+    let constraint = quote_identifier(&text(row, "constraint"));
+    SchemaItem {
+        kind,
+        name: text(row, "name"),
+        parent: Some(text(row, "table")),
+        create: Some(format!(
+            "ALTER TABLE {table} ADD CONSTRAINT {constraint} {definition};"
+        )),
+        drop: format!("ALTER TABLE {table} DROP CONSTRAINT {constraint};"),
+        definition,
+        alter: None,
+    }
+}
+
+fn index_item(row: &Value) -> SchemaItem {
+    let table = text(row, "quoted_table");
+    let index = quote_identifier(&text(row, "index"));
+
+    let mut definition = format!(
+        "CREATE {}{} INDEX {index} ON {table} ({})",
+        match flag(row, "unique") {
+            true => "UNIQUE ",
+            false => "",
+        },
+        text(row, "type"),
+        joined(row, "columns")
+    );
+    let included = joined(row, "included");
+    if !included.is_empty() {
+        definition.push_str(&format!(" INCLUDE ({included})"));
+    }
+    let filter = text(row, "filter");
+    if !filter.is_empty() {
+        definition.push_str(&format!(" WHERE {filter}"));
+    }
+
+    SchemaItem {
+        kind: "index",
+        name: text(row, "name"),
+        parent: Some(text(row, "table")),
+        create: Some(format!("{definition};")),
+        drop: format!("DROP INDEX {index} ON {table};"),
+        definition,
+        alter: None,
+    }
+}
+
+/// Compares two snapshots. Objects belonging to a table or column that was
+/// added or removed are reported through it alone, except foreign keys.
+pub fn schema_diff(source: &SchemaSnapshot, target: &SchemaSnapshot) -> Vec<SchemaDifference> {
+    let keys: BTreeSet<&(usize, String)> = source.items.keys().chain(target.items.keys()).collect();
+
+    let mut differences = vec![];
+    let mut whole_objects = BTreeSet::new();
+    for key in keys {
+        let before = target.get(key);
+        let after = source.get(key);
+        let change = match (before, after) {
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Removed,
+            (Some(before), Some(after)) if before.definition != after.definition => Change::Changed,
+            _ => continue,
+        };
+
+        let item = before.or(after).expect("one side exists");
+        let belongs_to_whole = item
+            .parent
+            .as_ref()
+            .is_some_and(|parent| whole_objects.contains(parent));
+        if change != Change::Changed && (item.kind == "table" || item.kind == "column") {
+            whole_objects.insert(item.name.clone());
+        }
+        // Foreign keys are kept, so that they are added once every table exists
+        // and dropped before any table is.
+        if belongs_to_whole && item.kind != "foreign key" {
+            continue;
+        }
+
+        differences.push(SchemaDifference {
+            kind: item.kind,
+            name: item.name.clone(),
+            change,
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+
+    differences
+}
+
+/// Builds a script applying `differences` to the target database.
+///
+/// Removed and changed objects are dropped first, in reverse dependency order,
+/// then added and changed objects are created. Columns are altered in place
+/// where possible. Added tables are scripted from the `source` connection,
+/// without their foreign keys, which follow as objects of their own. Identity
+/// changes cannot be applied in place and are written as comments.
+pub async fn migration_script(
+    source: &mut Client<TcpStream>,
+    differences: &[SchemaDifference],
+) -> Result<String, LabeledError> {
+    let mut drops = vec![];
+    let mut creates = vec![];
+
+    for difference in differences {
+        match (&difference.before, &difference.after) {
+            (Some(before), None) => drops.push(before.drop.clone()),
+            (Some(before), Some(after)) => match &after.alter {
+                Some(alter) => creates.push(format!("-- was: {}\n{alter}", before.definition)),
+                None => {
+                    drops.push(before.drop.clone());
+                    creates.push(create_statement(source, after).await?);
+                }
+            },
+            (None, Some(after)) => creates.push(create_statement(source, after).await?),
+            (None, None) => {}
+        }
+    }
+
+    drops.reverse();
+    let script = drops
+        .into_iter()
+        .chain(creates)
+        .map(|statement| format!("{}\nGO\n", statement.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(script)
+}
+
+async fn create_statement(
+    source: &mut Client<TcpStream>,
+    item: &SchemaItem,
+) -> Result<String, LabeledError> {
+    match &item.create {
+        Some(create) => Ok(create.clone()),
+        None => {
+            let script = script_object(source, &item.name, false).await?.script;
+            // The object scripts already end each batch with GO.
+            Ok(script.trim_end().trim_end_matches("GO").to_string())
+        }
+    }
+}
+
+fn joined(row: &Value, name: &str) -> String {
+    strings(row, name).join(", ")
+}
+
+#[test]
+fn test_schema_diff() {
+    let item =
+        |kind: &'static str, name: &str, parent: Option<&str>, definition: &str| SchemaItem {
+            kind,
+            name: name.to_string(),
+            parent: parent.map(str::to_string),
+            definition: definition.to_string(),
+            create: None,
+            drop: String::new(),
+            alter: None,
+        };
+
+    let mut source = SchemaSnapshot::default();
+    source.insert(item("table", "dbo.a", None, ""));
+    source.insert(item("column", "dbo.a.id", Some("dbo.a"), "int NOT NULL"));
+    source.insert(item(
+        "column",
+        "dbo.a.name",
+        Some("dbo.a"),
+        "nvarchar(50) NULL",
+    ));
+    source.insert(item(
+        "default",
+        "dbo.a.name",
+        Some("dbo.a.name"),
+        "DEFAULT ('new')",
+    ));
+    source.insert(item("table", "dbo.b", None, ""));
+    source.insert(item("column", "dbo.b.id", Some("dbo.b"), "int NOT NULL"));
+    source.insert(item(
+        "foreign key",
+        "dbo.b.FK_b_a",
+        Some("dbo.b"),
+        "FOREIGN KEY ([id]) REFERENCES [dbo].[a] ([id])",
+    ));
+
+    let mut target = SchemaSnapshot::default();
+    target.insert(item("table", "dbo.a", None, ""));
+    target.insert(item("column", "dbo.a.id", Some("dbo.a"), "int NOT NULL"));
+    target.insert(item(
+        "column",
+        "dbo.a.name",
+        Some("dbo.a"),
+        "nvarchar(20) NULL",
+    ));
+    target.insert(item(
+        "default",
+        "dbo.a.name",
+        Some("dbo.a.name"),
+        "DEFAULT ('old')",
+    ));
+    target.insert(item("column", "dbo.a.old", Some("dbo.a"), "int NULL"));
+    target.insert(item(
+        "default",
+        "dbo.a.old",
+        Some("dbo.a.old"),
+        "DEFAULT ((0))",
+    ));
+    target.insert(item(
+        "view",
+        "dbo.v",
+        None,
+        "CREATE VIEW dbo.v AS SELECT 1 AS x",
+    ));
+
+    let differences: Vec<(&str, String, &str)> = schema_diff(&source, &target)
+        .into_iter()
+        .map(|d| (d.kind, d.name, d.change.as_str()))
+        .collect();
+
+    assert_eq!(
+        differences,
+        vec![
+            ("table", "dbo.b".to_string(), "added"),
+            ("column", "dbo.a.name".to_string(), "changed"),
+            ("column", "dbo.a.old".to_string(), "removed"),
+            ("default", "dbo.a.name".to_string(), "changed"),
+            ("foreign key", "dbo.b.FK_b_a".to_string(), "added"),
+            ("view", "dbo.v".to_string(), "removed"),
+        ]
+    );
+}
+
+#[test]
+fn test_column_items() {
+    let span = Span::unknown();
+    let column = Value::record(
+        record! {
+            "table" => Value::string("dbo.a", span),
+            "quoted_table" => Value::string("[dbo].[a]", span),
+            "name" => Value::string("id", span),
+            "type_name" => Value::string("int", span),
+            "max_length" => Value::int(4, span),
+            "precision" => Value::int(10, span),
+            "scale" => Value::int(0, span),
+            "nullable" => Value::bool(false, span),
+            "default" => Value::string("((0))", span),
+            "default_name" => Value::string("DF_a_id", span),
+            "default_system_named" => Value::bool(false, span),
+            "seed" => Value::int(1, span),
+            "increment" => Value::int(1, span),
+        },
+        span,
+    );
+
+    let items = column_items(&column);
+    let kinds: Vec<&str> = items.iter().map(|item| item.kind).collect();
+    assert_eq!(kinds, vec!["column", "default", "identity"]);
+    assert_eq!(items[0].definition, "int NOT NULL");
+    assert_eq!(
+        items[0].create.as_deref(),
+        Some("ALTER TABLE [dbo].[a] ADD [id] int IDENTITY(1, 1) NOT NULL CONSTRAINT [DF_a_id] DEFAULT ((0));")
+    );
+    assert_eq!(
+        items[1].create.as_deref(),
+        Some("ALTER TABLE [dbo].[a] ADD CONSTRAINT [DF_a_id] DEFAULT ((0)) FOR [id];")
+    );
+    assert_eq!(
+        items[1].drop,
+        "ALTER TABLE [dbo].[a] DROP CONSTRAINT [DF_a_id];"
+    );
+    assert!(items[2]
+        .alter
+        .as_deref()
+        .is_some_and(|alter| alter.starts_with("--")));
+}
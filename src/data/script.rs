@@ -0,0 +1,479 @@
+use async_std::net::TcpStream;
+use nu_protocol::{LabeledError, Value};
+use tiberius::Client;
+
+use super::{describe, field, flag, int, list, query_records, quote_identifier, strings, text};
+
+/// A scripted database object.
+#[derive(Debug, Clone)]
+pub struct ObjectScript {
+    /// The schema qualified name, e.g. `dbo.Users`.
+    pub name: String,
+    /// The object type, e.g. `table` or `procedure`.
+    pub kind: &'static str,
+    pub script: String,
+    /// The `ALTER TABLE ... ADD CONSTRAINT` statements of a table's foreign
+    /// keys, to be run once every table they reference exists.
+    pub foreign_keys: String,
+    /// With `drop`, the statements dropping a table's foreign keys, to be run
+    /// before any table they reference is dropped.
+    pub drop_foreign_keys: String,
+}
+
+/// Maps a `sys.objects` type code to the keyword used in `CREATE` and `DROP`.
+pub(crate) fn object_kind(type_code: &str) -> Option<&'static str> {
+    match type_code.trim() {
+        "U" => Some("table"),
+        "V" => Some("view"),
+        "P" => Some("procedure"),
+        "FN" | "IF" | "TF" => Some("function"),
+        "TR" => Some("trigger"),
+        _ => None,
+    }
+}
+
+/// Lists the scriptable objects of `schema` as quoted names, in an order the
+/// scripts can be run in: tables first, then views, functions and procedures,
+/// each after the objects it references according to
+/// `sys.sql_expression_dependencies`.
+pub async fn schema_objects(
+    client: &mut Client<TcpStream>,
+    schema: &str,
+) -> Result<Vec<String>, LabeledError> {
+    let objects = query_records(
+        client,
+        "SELECT o.object_id AS id, QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS name
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE s.name = @P1 AND o.is_ms_shipped = 0
+            AND o.type IN ('U', 'V', 'FN', 'IF', 'TF', 'P')
+        ORDER BY CASE o.type WHEN 'U' THEN 0 WHEN 'V' THEN 1 WHEN 'P' THEN 3 ELSE 2 END, o.name",
+        &[&schema],
+    )
+    .await?;
+    let references = query_records(
+        client,
+        "SELECT DISTINCT d.referencing_id, d.referenced_id
+        FROM sys.sql_expression_dependencies d
+        JOIN sys.objects o ON o.object_id = d.referencing_id
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE s.name = @P1 AND d.referenced_id IS NOT NULL
+            AND d.referenced_id <> d.referencing_id",
+        &[&schema],
+    )
+    .await?;
+
+    let objects: Vec<(i64, String)> = objects
+        .iter()
+        .map(|row| (int(row, "id"), text(row, "name")))
+        .collect();
+    let references: Vec<(i64, i64)> = references
+        .iter()
+        .map(|row| (int(row, "referencing_id"), int(row, "referenced_id")))
+        .collect();
+    Ok(dependency_order(objects, &references))
+}
+
+/// Orders `objects` so that each comes after the objects it references,
+/// keeping their given order where the references allow. Objects in a cycle
+/// keep their given order.
+fn dependency_order(objects: Vec<(i64, String)>, references: &[(i64, i64)]) -> Vec<String> {
+    let mut pending = objects;
+    let mut ordered = vec![];
+    while !pending.is_empty() {
+        let waiting = |id: i64| {
+            references.iter().any(|(referencing, referenced)| {
+                *referencing == id
+                    && pending
+                        .iter()
+                        .any(|(other, _)| other == referenced && *other != id)
+            })
+        };
+        let next = pending
+            .iter()
+            .position(|(id, _)| !waiting(*id))
+            .unwrap_or(0);
+        ordered.push(pending.remove(next).1);
+    }
+    ordered
+}
+
+/// Scripts the `CREATE` statement of `object`, optionally preceded by a
+/// `DROP ... IF EXISTS`. Batches are separated with `GO`.
+///
+/// Tables are reconstructed from the catalog views, everything else is read
+/// back with `OBJECT_DEFINITION`. The foreign keys of a table are scripted
+/// apart from it, so that tables referencing each other can all be created
+/// before any key is added.
+pub async fn script_object(
+    client: &mut Client<TcpStream>,
+    object: &str,
+    drop: bool,
+) -> Result<ObjectScript, LabeledError> {
+    let row = query_records(
+        client,
+        "SELECT s.name + '.' + o.name AS name, QUOTENAME(s.name) + '.' + QUOTENAME(o.name) AS quoted,
+            o.type, OBJECT_DEFINITION(o.object_id) AS definition
+        FROM sys.objects o
+        JOIN sys.schemas s ON s.schema_id = o.schema_id
+        WHERE o.object_id = OBJECT_ID(@P1)",
+        &[&object],
+    )
+    .await?
+    .pop()
+    .ok_or_else(|| LabeledError::new(format!("Object {object} does not exist")))?;
+
+    let name = text(&row, "name");
+    let quoted = text(&row, "quoted");
+    let type_code = text(&row, "type");
+    let kind = object_kind(&type_code).ok_or_else(|| {
+        LabeledError::new(format!(
+            "Object {name} has type {type_code}, which cannot be scripted"
+        ))
+    })?;
+
+    let mut script = String::new();
+    if drop {
+        script.push_str(&format!(
+            "DROP {} IF EXISTS {};\nGO\n\n",
+            kind.to_uppercase(),
+            quoted
+        ));
+    }
+
+    let mut foreign_keys = String::new();
+    let mut drop_foreign_keys = String::new();
+    if kind == "table" {
+        let (table, keys) = script_table(client, &quoted).await?;
+        script.push_str(&table);
+        foreign_keys = keys;
+
+        if drop {
+            let names = query_records(
+                client,
+                "SELECT name FROM sys.foreign_keys WHERE parent_object_id = OBJECT_ID(@P1) ORDER BY name",
+                &[&quoted.as_str()],
+            )
+            .await?;
+            for row in names {
+                drop_foreign_keys.push_str(&format!(
+                    "IF OBJECT_ID(N'{}', 'U') IS NOT NULL\n    \
+                    ALTER TABLE {quoted} DROP CONSTRAINT IF EXISTS {};\nGO\n",
+                    quoted.replace('\'', "''"),
+                    quote_identifier(&text(&row, "name"))
+                ));
+            }
+        }
+    } else {
+        let definition = text(&row, "definition");
+        if definition.is_empty() {
+            return Err(LabeledError::new(format!(
+                "The definition of {name} is encrypted or not visible to this user"
+            )));
+        }
+        script.push_str(definition.trim());
+        script.push_str("\nGO\n");
+    }
+
+    Ok(ObjectScript {
+        name,
+        kind,
+        script,
+        foreign_keys,
+        drop_foreign_keys,
+    })
+}
+
+/// Scripts `table`, given as a quoted name, returning its `CREATE TABLE`,
+/// indexes and triggers, and apart from them its foreign keys.
+async fn script_table(
+    client: &mut Client<TcpStream>,
+    table: &str,
+) -> Result<(String, String), LabeledError> {
+    let description = describe(client, table).await?;
+    let identities = query_records(
+        client,
+        "SELECT name, CAST(seed_value AS BIGINT) AS seed, CAST(increment_value AS BIGINT) AS increment
+        FROM sys.identity_columns
+        WHERE object_id = OBJECT_ID(@P1)",
+        &[&table],
+    )
+    .await?;
+    let defaults = query_records(
+        client,
+        "SELECT c.name AS [column], d.name
+        FROM sys.default_constraints d
+        JOIN sys.columns c ON c.object_id = d.parent_object_id AND c.column_id = d.parent_column_id
+        WHERE d.parent_object_id = OBJECT_ID(@P1)",
+        &[&table],
+    )
+    .await?;
+    let references = query_records(
+        client,
+        "SELECT name, QUOTENAME(OBJECT_SCHEMA_NAME(referenced_object_id)) + '.'
+            + QUOTENAME(OBJECT_NAME(referenced_object_id)) AS [references]
+        FROM sys.foreign_keys
+        WHERE parent_object_id = OBJECT_ID(@P1)",
+        &[&table],
+    )
+    .await?;
+    let indexes = list(&description, "indexes");
+    let index_keys = |name: &str| {
+        indexes
+            .iter()
+            .find(|index| text(index, "name") == name)
+            .map(key_list)
+            .unwrap_or_default()
+    };
+
+    let mut lines = vec![];
+    for column in list(&description, "columns") {
+        let name = text(&column, "name");
+        let mut line = format!("    {}", quote_identifier(&name));
+
+        if flag(&column, "computed") {
+            line.push_str(&format!(" AS {}", text(&column, "computed_definition")));
+            if flag(&column, "persisted") {
+                line.push_str(" PERSISTED");
+            }
+            lines.push(line);
+            continue;
+        }
+
+        line.push(' ');
+        line.push_str(&text(&column, "type"));
+        let collation = text(&column, "collation");
+        if !collation.is_empty() {
+            line.push_str(&format!(" COLLATE {collation}"));
+        }
+        if let Some(identity) = identities.iter().find(|row| text(row, "name") == name) {
+            line.push_str(&format!(
+                " IDENTITY({}, {})",
+                int(identity, "seed"),
+                int(identity, "increment")
+            ));
+        }
+        line.push_str(match flag(&column, "nullable") {
+            true => " NULL",
+            false => " NOT NULL",
+        });
+        let default = text(&column, "default");
+        if !default.is_empty() {
+            if let Some(constraint) = defaults.iter().find(|row| text(row, "column") == name) {
+                line.push_str(&format!(
+                    " CONSTRAINT {}",
+                    quote_identifier(&text(constraint, "name"))
+                ));
+            }
+            line.push_str(&format!(" DEFAULT {default}"));
+        }
+        lines.push(line);
+    }
+
+    let primary_key = field(&description, "primary_key");
+    if !primary_key.is_nothing() {
+        let name = text(&primary_key, "name");
+        lines.push(format!(
+            "    CONSTRAINT {} PRIMARY KEY {} ({})",
+            quote_identifier(&name),
+            match flag(&primary_key, "clustered") {
+                true => "CLUSTERED",
+                false => "NONCLUSTERED",
+            },
+            index_keys(&name)
+        ));
+    }
+    for unique in list(&description, "unique_constraints") {
+        let name = text(&unique, "name");
+        lines.push(format!(
+            "    CONSTRAINT {} UNIQUE ({})",
+            quote_identifier(&name),
+            index_keys(&name)
+        ));
+    }
+    for check in list(&description, "check_constraints") {
+        lines.push(format!(
+            "    CONSTRAINT {} CHECK {}",
+            quote_identifier(&text(&check, "name")),
+            text(&check, "definition")
+        ));
+    }
+
+    let mut script = format!("CREATE TABLE {table} (\n{}\n);\nGO\n", lines.join(",\n"));
+    // Constraints created with the table are enabled and trusted. Disabling one
+    // and enabling it again without WITH CHECK leaves it untrusted.
+    let mut checks = String::new();
+    for check in list(&description, "check_constraints") {
+        let name = quote_identifier(&text(&check, "name"));
+        if flag(&check, "disabled") || flag(&check, "not_trusted") {
+            checks.push_str(&format!("ALTER TABLE {table} NOCHECK CONSTRAINT {name};\n"));
+        }
+        if !flag(&check, "disabled") && flag(&check, "not_trusted") {
+            checks.push_str(&format!("ALTER TABLE {table} CHECK CONSTRAINT {name};\n"));
+        }
+    }
+    if !checks.is_empty() {
+        script.push_str(&format!("\n{checks}GO\n"));
+    }
+
+    let mut foreign_keys = String::new();
+    for foreign_key in list(&description, "foreign_keys") {
+        let name = text(&foreign_key, "name");
+        let referenced = references
+            .iter()
+            .find(|row| text(row, "name") == name)
+            .map(|row| text(row, "references"))
+            .unwrap_or_default();
+        let mut line = format!(
+            "ALTER TABLE {table} {}ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {referenced} ({})",
+            match flag(&foreign_key, "not_trusted") {
+                true => "WITH NOCHECK ",
+                false => "",
+            },
+            quote_identifier(&name),
+            column_list(&foreign_key, "columns"),
+            column_list(&foreign_key, "referenced_columns")
+        );
+        for (clause, field) in [("ON DELETE", "on_delete"), ("ON UPDATE", "on_update")] {
+            let action = text(&foreign_key, field);
+            if action != "no_action" {
+                line.push_str(&format!(
+                    " {clause} {}",
+                    action.replace('_', " ").to_uppercase()
+                ));
+            }
+        }
+        line.push(';');
+        if flag(&foreign_key, "disabled") {
+            line.push_str(&format!(
+                "\nALTER TABLE {table} NOCHECK CONSTRAINT {};",
+                quote_identifier(&name)
+            ));
+        }
+        foreign_keys.push_str(&format!("{line}\nGO\n"));
+    }
+
+    for index in &indexes {
+        let name = quote_identifier(&text(index, "name"));
+        if flag(index, "primary_key") {
+            continue;
+        }
+        let is_unique_constraint = list(&description, "unique_constraints")
+            .iter()
+            .any(|unique| text(unique, "name") == text(index, "name"));
+        if is_unique_constraint {
+            continue;
+        }
+
+        let kind = text(index, "type");
+        if kind != "clustered" && kind != "nonclustered" {
+            script.push_str(&format!(
+                "\n-- Index {name} of type {kind} is not scripted\n"
+            ));
+            continue;
+        }
+
+        script.push_str(&format!(
+            "\nCREATE {}{} INDEX {name} ON {table} ({})",
+            match flag(index, "unique") {
+                true => "UNIQUE ",
+                false => "",
+            },
+            kind.to_uppercase(),
+            key_list(index)
+        ));
+        if !list(index, "included").is_empty() {
+            script.push_str(&format!(" INCLUDE ({})", column_list(index, "included")));
+        }
+        let filter = text(index, "filter");
+        if !filter.is_empty() {
+            script.push_str(&format!(" WHERE {filter}"));
+        }
+        script.push_str(";\nGO\n");
+    }
+
+    for trigger in list(&description, "triggers") {
+        let name = text(&trigger, "name");
+        let definition = query_records(
+            client,
+            "SELECT OBJECT_DEFINITION(object_id) AS definition
+            FROM sys.triggers
+            WHERE parent_id = OBJECT_ID(@P1) AND name = @P2",
+            &[&table, &name.as_str()],
+        )
+        .await?
+        .pop()
+        .map(|row| text(&row, "definition"))
+        .unwrap_or_default();
+        if !definition.is_empty() {
+            script.push_str(&format!("\n{}\nGO\n", definition.trim()));
+        }
+    }
+
+    Ok((script, foreign_keys))
+}
+
+fn column_list(row: &Value, name: &str) -> String {
+    strings(row, name)
+        .iter()
+        .map(|name| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Lists the key columns of an index with their direction.
+fn key_list(index: &Value) -> String {
+    let descending = list(index, "descending");
+    strings(index, "columns")
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            match descending
+                .get(i)
+                .is_some_and(|value| value.as_bool().unwrap_or_default())
+            {
+                true => format!("{} DESC", quote_identifier(name)),
+                false => quote_identifier(name),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[test]
+fn test_key_list() {
+    use nu_protocol::{record, Span};
+
+    let span = Span::unknown();
+    let index = Value::record(
+        record! {
+            "columns" => Value::list(
+                vec![Value::string("Created", span), Value::string("Id", span)],
+                span,
+            ),
+            "descending" => Value::list(
+                vec![Value::bool(true, span), Value::bool(false, span)],
+                span,
+            ),
+        },
+        span,
+    );
+    assert_eq!(key_list(&index), "[Created] DESC, [Id]");
+}
+
+#[test]
+fn test_dependency_order() {
+    let objects = vec![
+        (1, "t".to_string()),
+        (2, "v_outer".to_string()),
+        (3, "v_inner".to_string()),
+        (4, "f".to_string()),
+        (5, "p".to_string()),
+    ];
+    // v_outer reads v_inner, v_inner calls f, p reads v_outer and calls itself.
+    let references = [(2, 3), (3, 4), (3, 1), (5, 2), (5, 5)];
+    assert_eq!(
+        dependency_order(objects, &references),
+        vec!["t", "f", "v_inner", "v_outer", "p"]
+    );
+}
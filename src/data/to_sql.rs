@@ -0,0 +1,450 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use nu_protocol::{LabeledError, Value};
+use tiberius::{
+    numeric::Numeric,
+    time::{Date, DateTime2, DateTimeOffset, SmallDateTime, Time},
+    xml::XmlData,
+    ColumnData, ToSql, Uuid,
+};
+
+use super::{to_json, TableColumn};
+
+/// A converted cell that can be bound as a query parameter.
+#[derive(Debug, Clone)]
+pub struct SqlParam(pub ColumnData<'static>);
+
+impl ToSql for SqlParam {
+    fn to_sql(&self) -> ColumnData<'_> {
+        self.0.clone()
+    }
+}
+
+/// Converts a Nushell value into the representation SQL Server expects for `column`.
+#[allow(clippy::result_large_err)]
+pub fn to_column_data(
+    value: &Value,
+    column: &TableColumn,
+) -> Result<ColumnData<'static>, LabeledError> {
+    if matches!(value, Value::Nothing { .. }) {
+        if !column.nullable {
+            return Err(
+                LabeledError::new(format!("Column {} does not allow nulls", column.name))
+                    .with_label("missing value", value.span()),
+            );
+        }
+        return Ok(null_for(column));
+    }
+
+    let converted = match column.sql_type.as_str() {
+        "bit" => to_bool(value).map(|val| ColumnData::Bit(Some(val))),
+        "tinyint" => to_int(value)
+            .and_then(|val| u8::try_from(val).ok())
+            .map(|val| ColumnData::U8(Some(val))),
+        "smallint" => to_int(value)
+            .and_then(|val| i16::try_from(val).ok())
+            .map(|val| ColumnData::I16(Some(val))),
+        "int" => to_int(value)
+            .and_then(|val| i32::try_from(val).ok())
+            .map(|val| ColumnData::I32(Some(val))),
+        "bigint" => to_int(value).map(|val| ColumnData::I64(Some(val))),
+        "real" => to_float(value).map(|val| ColumnData::F32(Some(val as f32))),
+        "float" => to_float(value).map(|val| ColumnData::F64(Some(val))),
+        "decimal" | "numeric" => to_decimal(value, column.scale)
+            .map(|val| ColumnData::Numeric(Some(Numeric::new_with_scale(val, column.scale)))),
+        // Sent as a decimal with the four places of money, which the server converts.
+        "money" | "smallmoney" => to_decimal(value, 4)
+            .map(|val| ColumnData::Numeric(Some(Numeric::new_with_scale(val, 4)))),
+        "char" | "varchar" | "nchar" | "nvarchar" | "text" | "ntext" => {
+            to_text(value).map(|val| ColumnData::String(Some(Cow::Owned(val))))
+        }
+        "xml" => to_text(value).map(|val| ColumnData::Xml(Some(Cow::Owned(XmlData::new(val))))),
+        "uniqueidentifier" => value
+            .as_str()
+            .ok()
+            .and_then(|val| Uuid::parse_str(val).ok())
+            .map(|val| ColumnData::Guid(Some(val))),
+        "binary" | "varbinary" | "image" => match value {
+            Value::Binary { val, .. } => Some(ColumnData::Binary(Some(Cow::Owned(val.clone())))),
+            _ => None,
+        },
+        "date" => {
+            to_datetime(value).map(|val| ColumnData::Date(Some(to_date(val.naive_local().date()))))
+        }
+        "time" => {
+            to_time_of_day(value).map(|val| ColumnData::Time(Some(to_time(val, column.scale))))
+        }
+        "datetime2" => to_datetime(value).map(|val| {
+            let naive = val.naive_local();
+            ColumnData::DateTime2(Some(DateTime2::new(
+                to_date(naive.date()),
+                to_time(naive.time(), 7),
+            )))
+        }),
+        "datetimeoffset" => to_datetime(value).map(|val| {
+            let naive = val.naive_utc();
+            let offset = (val.offset().local_minus_utc() / 60) as i16;
+            ColumnData::DateTimeOffset(Some(DateTimeOffset::new(
+                DateTime2::new(to_date(naive.date()), to_time(naive.time(), column.scale)),
+                offset,
+            )))
+        }),
+        "datetime" => to_datetime(value).map(|val| {
+            let naive = val.naive_local();
+            let days = days_since(naive.date(), 1900) as i32;
+            let nanos = nanos_since_midnight(naive.time());
+            // DATETIME stores time as 1/300th of a second increments.
+            let fragments = (nanos * 300 / 1_000_000_000) as u32;
+            ColumnData::DateTime(Some(tiberius::time::DateTime::new(days, fragments)))
+        }),
+        "smalldatetime" => to_datetime(value).map(|val| {
+            let naive = val.naive_local();
+            let days = days_since(naive.date(), 1900) as u16;
+            let minutes = (naive.time().num_seconds_from_midnight() / 60) as u16;
+            ColumnData::SmallDateTime(Some(SmallDateTime::new(days, minutes)))
+        }),
+        other => {
+            return Err(LabeledError::new(format!(
+                "Column {} has unsupported type {other}",
+                column.name
+            ))
+            .with_label("cannot be converted", value.span()))
+        }
+    };
+
+    converted.ok_or_else(|| {
+        LabeledError::new(format!(
+            "Cannot convert {} to {} for column {}",
+            value.get_type(),
+            column.sql_type,
+            column.name
+        ))
+        .with_label("invalid value", value.span())
+    })
+}
+
+/// Converts a Nushell value into a parameter value, choosing the SQL type from the value.
+#[allow(clippy::result_large_err)]
+pub fn infer_column_data(value: &Value) -> Result<ColumnData<'static>, LabeledError> {
+    let converted = match value {
+        // Untyped NULL parameters are rejected, NVARCHAR converts implicitly to anything.
+        Value::Nothing { .. } => ColumnData::String(None),
+        Value::Bool { val, .. } => ColumnData::Bit(Some(*val)),
+        Value::Int { val, .. } | Value::Filesize { val, .. } | Value::Duration { val, .. } => {
+            ColumnData::I64(Some(*val))
+        }
+        Value::Float { val, .. } => ColumnData::F64(Some(*val)),
+        Value::String { val, .. } | Value::Glob { val, .. } => {
+            ColumnData::String(Some(Cow::Owned(val.clone())))
+        }
+        Value::Binary { val, .. } => ColumnData::Binary(Some(Cow::Owned(val.clone()))),
+        Value::Date { val, .. } => {
+            let naive = val.naive_utc();
+            let offset = (val.offset().local_minus_utc() / 60) as i16;
+            ColumnData::DateTimeOffset(Some(DateTimeOffset::new(
+                DateTime2::new(to_date(naive.date()), to_time(naive.time(), 7)),
+                offset,
+            )))
+        }
+        Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+        other => {
+            return Err(LabeledError::new(format!(
+                "Cannot use {} as a parameter",
+                other.get_type()
+            ))
+            .with_label("unsupported parameter type", other.span()))
+        }
+    };
+
+    Ok(converted)
+}
+
+/// The SQL type to declare for a parameter created by [`infer_column_data`].
+pub fn parameter_type(data: &ColumnData<'_>) -> &'static str {
+    match data {
+        ColumnData::Bit(_) => "bit",
+        ColumnData::U8(_) => "tinyint",
+        ColumnData::I16(_) => "smallint",
+        ColumnData::I32(_) => "int",
+        ColumnData::I64(_) => "bigint",
+        ColumnData::F32(_) => "real",
+        ColumnData::F64(_) => "float",
+        ColumnData::Guid(_) => "uniqueidentifier",
+        ColumnData::Binary(_) => "varbinary(max)",
+        ColumnData::Numeric(_) => "decimal(38, 10)",
+        ColumnData::Xml(_) => "xml",
+        ColumnData::DateTime(_) => "datetime",
+        ColumnData::SmallDateTime(_) => "smalldatetime",
+        ColumnData::Time(_) => "time(7)",
+        ColumnData::Date(_) => "date",
+        ColumnData::DateTime2(_) => "datetime2(7)",
+        ColumnData::DateTimeOffset(_) => "datetimeoffset(7)",
+        ColumnData::String(_) => "nvarchar(max)",
+    }
+}
+
+/// Converts a record into one cell per column, matching fields to columns by name.
+///
+/// Columns missing from the record are sent as NULL; fields without a matching
+/// column are an error so typos don't silently drop data.
+#[allow(clippy::result_large_err)]
+pub fn to_row(
+    value: &Value,
+    columns: &[TableColumn],
+) -> Result<Vec<ColumnData<'static>>, LabeledError> {
+    let record = match value {
+        Value::Record { val, .. } => val,
+        Value::Error { error, .. } => return Err(LabeledError::from(*error.clone())),
+        other => {
+            return Err(LabeledError::new(format!(
+                "Expected a record but got {}",
+                other.get_type()
+            ))
+            .with_label("not a record", other.span()))
+        }
+    };
+
+    for (name, field) in record.iter() {
+        if !columns
+            .iter()
+            .any(|column| column.name.eq_ignore_ascii_case(name))
+        {
+            return Err(LabeledError::new(format!("Column {name} does not exist"))
+                .with_label("no matching column", field.span()));
+        }
+    }
+
+    columns
+        .iter()
+        .map(|column| {
+            let field = record
+                .get(&column.name)
+                .or_else(|| {
+                    record
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(&column.name))
+                        .map(|(_, value)| value)
+                })
+                .unwrap_or(&Value::nothing(value.span()))
+                .clone();
+            to_column_data(&field, column)
+        })
+        .collect()
+}
+
+fn null_for(column: &TableColumn) -> ColumnData<'static> {
+    match column.sql_type.as_str() {
+        "bit" => ColumnData::Bit(None),
+        "tinyint" => ColumnData::U8(None),
+        "smallint" => ColumnData::I16(None),
+        "int" => ColumnData::I32(None),
+        "bigint" => ColumnData::I64(None),
+        "real" => ColumnData::F32(None),
+        "float" => ColumnData::F64(None),
+        "decimal" | "numeric" | "money" | "smallmoney" => ColumnData::Numeric(None),
+        "xml" => ColumnData::Xml(None),
+        "uniqueidentifier" => ColumnData::Guid(None),
+        "binary" | "varbinary" | "image" => ColumnData::Binary(None),
+        "date" => ColumnData::Date(None),
+        "time" => ColumnData::Time(None),
+        "datetime2" => ColumnData::DateTime2(None),
+        "datetimeoffset" => ColumnData::DateTimeOffset(None),
+        "datetime" => ColumnData::DateTime(None),
+        "smalldatetime" => ColumnData::SmallDateTime(None),
+        _ => ColumnData::String(None),
+    }
+}
+
+fn to_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool { val, .. } => Some(*val),
+        Value::Int { val, .. } => Some(*val != 0),
+        Value::String { val, .. } => match val.trim().to_lowercase().as_str() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn to_int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int { val, .. } => Some(*val),
+        Value::Bool { val, .. } => Some(*val as i64),
+        // Durations are stored as nanoseconds, as `mssql create-table` declares them.
+        Value::Filesize { val, .. } | Value::Duration { val, .. } => Some(*val),
+        Value::Float { val, .. } if val.fract() == 0.0 => Some(*val as i64),
+        Value::String { val, .. } => val.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float { val, .. } => Some(*val),
+        Value::Int { val, .. } => Some(*val as f64),
+        Value::String { val, .. } => val.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_decimal(value: &Value, scale: u8) -> Option<i128> {
+    match value {
+        Value::Int { val, .. } => (*val as i128).checked_mul(10i128.pow(scale as u32)),
+        Value::Float { val, .. } => Some((val * 10f64.powi(scale as i32)).round() as i128),
+        Value::String { val, .. } => parse_decimal(val, scale),
+        _ => None,
+    }
+}
+
+/// Parses a decimal literal into an integer scaled by `10^scale`, rounding extra digits.
+fn parse_decimal(text: &str, scale: u8) -> Option<i128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let mut scaled: i128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let mut fraction = fraction.chars();
+    for _ in 0..scale {
+        let digit = fraction.next().and_then(|c| c.to_digit(10)).unwrap_or(0);
+        scaled = scaled.checked_mul(10)?.checked_add(digit as i128)?;
+    }
+    if fraction.next().is_some_and(|c| c >= '5') {
+        scaled = scaled.checked_add(1)?;
+    }
+
+    Some(if negative { -scaled } else { scaled })
+}
+
+fn to_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String { val, .. } | Value::Glob { val, .. } => Some(val.clone()),
+        Value::Int { val, .. } => Some(val.to_string()),
+        Value::Float { val, .. } => Some(val.to_string()),
+        Value::Bool { val, .. } => Some(val.to_string()),
+        Value::Date { val, .. } => Some(val.to_rfc3339()),
+        Value::Record { .. } | Value::List { .. } => Some(to_json(value.clone()).to_string()),
+        _ => None,
+    }
+}
+
+fn to_datetime(value: &Value) -> Option<DateTime<FixedOffset>> {
+    match value {
+        Value::Date { val, .. } => Some(*val),
+        Value::String { val, .. } => parse_datetime(val),
+        _ => None,
+    }
+}
+
+/// Parses the date and time formats SQL Server itself accepts as literals.
+pub(crate) fn parse_datetime(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    let utc = FixedOffset::east_opt(0)?;
+
+    if let Ok(val) = DateTime::parse_from_rfc3339(text) {
+        return Some(val);
+    }
+    if let Ok(val) = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f %:z") {
+        return Some(val);
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(val) = NaiveDateTime::parse_from_str(text, format) {
+            return val.and_local_timezone(utc).single();
+        }
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_time(NaiveTime::MIN))
+        .and_then(|val| val.and_local_timezone(utc).single())
+}
+
+fn to_time_of_day(value: &Value) -> Option<NaiveTime> {
+    match value {
+        Value::Duration { val, .. } if (0..86_400_000_000_000).contains(val) => {
+            let secs = (val / 1_000_000_000) as u32;
+            let nanos = (val % 1_000_000_000) as u32;
+            NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos)
+        }
+        Value::Date { val, .. } => Some(val.time()),
+        Value::String { val, .. } => NaiveTime::parse_from_str(val.trim(), "%H:%M:%S%.f")
+            .or_else(|_| NaiveTime::parse_from_str(val.trim(), "%H:%M"))
+            .ok(),
+        _ => None,
+    }
+}
+
+fn days_since(date: NaiveDate, year: i32) -> i64 {
+    let start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default();
+    date.signed_duration_since(start).num_days()
+}
+
+fn nanos_since_midnight(time: NaiveTime) -> u64 {
+    time.num_seconds_from_midnight() as u64 * 1_000_000_000 + time.nanosecond() as u64
+}
+
+fn to_date(date: NaiveDate) -> Date {
+    Date::new(days_since(date, 1) as u32)
+}
+
+fn to_time(time: NaiveTime, scale: u8) -> Time {
+    let increments = nanos_since_midnight(time) / 10u64.pow(9 - scale as u32);
+    Time::new(increments, scale)
+}
+
+#[test]
+fn test_parse_decimal() {
+    assert_eq!(parse_decimal("12.345", 2), Some(1235));
+    assert_eq!(parse_decimal("-0.5", 3), Some(-500));
+    assert_eq!(parse_decimal("7", 1), Some(70));
+    assert_eq!(parse_decimal(".25", 2), Some(25));
+    assert_eq!(parse_decimal("1e5", 0), None);
+    assert_eq!(parse_decimal("", 2), None);
+}
+
+#[test]
+fn test_money_column_data() {
+    let column = TableColumn {
+        name: "Price".to_string(),
+        sql_type: "money".to_string(),
+        max_length: 8,
+        precision: 19,
+        scale: 4,
+        nullable: true,
+        identity: false,
+        computed: false,
+    };
+    let span = nu_protocol::Span::test_data();
+    let expected = Numeric::new_with_scale(123_450, 4);
+    for value in [Value::string("12.345", span), Value::float(12.345, span)] {
+        match to_column_data(&value, &column).unwrap() {
+            ColumnData::Numeric(Some(numeric)) => assert_eq!(numeric, expected),
+            other => panic!("Unexpected {other:?}"),
+        }
+    }
+    assert!(matches!(
+        to_column_data(&Value::nothing(span), &column).unwrap(),
+        ColumnData::Numeric(None)
+    ));
+}
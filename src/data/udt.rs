@@ -0,0 +1,741 @@
+use async_std::net::TcpStream;
+use nu_protocol::{record, LabeledError, Span, Value};
+use tiberius::{
+    time::chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime},
+    Client,
+};
+
+use super::{describe_result, keyword_positions, quote_identifier, ResultColumn};
+
+/// A column type that tiberius cannot receive, which is sent as another type
+/// and decoded once the row arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrappedType {
+    Geometry,
+    Geography,
+    HierarchyId,
+    Variant,
+}
+
+impl WrappedType {
+    fn from_type_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "geometry" => Some(Self::Geometry),
+            "geography" => Some(Self::Geography),
+            "hierarchyid" => Some(Self::HierarchyId),
+            "sql_variant" => Some(Self::Variant),
+            _ => None,
+        }
+    }
+
+    /// The expression the column is selected as instead.
+    ///
+    /// CLR types are sent in their native serialization, and a `sql_variant`
+    /// as its base type name and its value as text, separated by a colon.
+    fn projection(self, column: &str) -> String {
+        match self {
+            Self::Geometry | Self::Geography | Self::HierarchyId => {
+                format!("CAST({column} AS varbinary(max))")
+            }
+            Self::Variant => format!(
+                "CAST(SQL_VARIANT_PROPERTY({column}, 'BaseType') AS nvarchar(128)) + N':' +
+                CASE CAST(SQL_VARIANT_PROPERTY({column}, 'BaseType') AS nvarchar(128))
+                    WHEN 'binary' THEN CONVERT(nvarchar(max), CAST({column} AS varbinary(8000)), 2)
+                    WHEN 'varbinary' THEN CONVERT(nvarchar(max), CAST({column} AS varbinary(8000)), 2)
+                    WHEN 'float' THEN CONVERT(nvarchar(max), CAST({column} AS float), 3)
+                    WHEN 'real' THEN CONVERT(nvarchar(max), CAST({column} AS real), 3)
+                    WHEN 'money' THEN CONVERT(nvarchar(max), CAST({column} AS money), 2)
+                    WHEN 'smallmoney' THEN CONVERT(nvarchar(max), CAST({column} AS money), 2)
+                    WHEN 'date' THEN CONVERT(nvarchar(max), CAST({column} AS date), 126)
+                    WHEN 'datetime' THEN CONVERT(nvarchar(max), CAST({column} AS datetime2), 126)
+                    WHEN 'datetime2' THEN CONVERT(nvarchar(max), CAST({column} AS datetime2), 126)
+                    WHEN 'smalldatetime' THEN CONVERT(nvarchar(max), CAST({column} AS datetime2), 126)
+                    WHEN 'datetimeoffset' THEN CONVERT(nvarchar(max), CAST({column} AS datetimeoffset), 126)
+                    ELSE CAST({column} AS nvarchar(max))
+                END"
+            ),
+        }
+    }
+}
+
+/// How `geometry` and `geography` values are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpatialFormat {
+    /// Well-known text, e.g. `POINT (1 2)`.
+    #[default]
+    Wkt,
+    /// A GeoJSON geometry record with an added `srid` field.
+    GeoJson,
+}
+
+impl SpatialFormat {
+    #[allow(clippy::result_large_err)]
+    pub fn parse(format: &str) -> Result<Self, LabeledError> {
+        match format.to_lowercase().as_str() {
+            "wkt" => Ok(Self::Wkt),
+            "geojson" => Ok(Self::GeoJson),
+            _ => Err(LabeledError::new(format!(
+                "Unknown spatial format {format}, expected wkt or geojson"
+            ))),
+        }
+    }
+}
+
+/// Rewrites `sql` so that its `geometry`, `geography`, `hierarchyid` and
+/// `sql_variant` columns can be received, returning the query to run and the
+/// columns to decode. `described` is the [`describe_result`] of `sql`.
+///
+/// tiberius fails on these types, so the query is selected from as a derived
+/// table with those columns converted. Queries without such columns, or that
+/// the server cannot describe, are returned unchanged. A trailing `ORDER BY`
+/// is moved to the outer query, or kept inside with `OFFSET 0 ROWS` when it
+/// refers to names only the inner query knows. Queries that still cannot be
+/// used as a derived table, e.g. with a `WITH` clause, several statements or
+/// an `EXEC`, are an error asking for the columns to be cast in the query
+/// instead.
+pub async fn unwrap_types(
+    client: &mut Client<TcpStream>,
+    sql: &str,
+    described: Option<&[ResultColumn]>,
+) -> Result<(String, Vec<(String, WrappedType)>), LabeledError> {
+    let Some(columns) = described else {
+        return Ok((sql.to_string(), vec![]));
+    };
+
+    let wrapped: Vec<(String, WrappedType)> = columns
+        .iter()
+        .filter_map(|column| {
+            WrappedType::from_type_name(&column.sql_type).map(|ty| (column.name.clone(), ty))
+        })
+        .collect();
+    if wrapped.is_empty() {
+        return Ok((sql.to_string(), vec![]));
+    }
+
+    for (i, column) in columns.iter().enumerate() {
+        if column.name.is_empty()
+            || columns[..i]
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&column.name))
+        {
+            return Err(LabeledError::new(format!(
+                "Every column must have a unique name to decode {} columns",
+                wrapped[0].0
+            )));
+        }
+    }
+
+    let projection = columns
+        .iter()
+        .map(|column| {
+            let name = quote_identifier(&column.name);
+            let qualified = format!("[mssql_result].{name}");
+            match WrappedType::from_type_name(&column.sql_type) {
+                Some(ty) => format!("{} AS {name}", ty.projection(&qualified)),
+                None => qualified,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",\n    ");
+    for rewritten in derived_queries(sql, &projection) {
+        if describe_result(client, &rewritten).await.is_some() {
+            return Ok((rewritten, wrapped));
+        }
+    }
+
+    let names = wrapped
+        .iter()
+        .map(|(name, _)| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(LabeledError::new(format!(
+        "Cannot convert the geometry, geography, hierarchyid or sql_variant columns {names} \
+            of this query, CAST them in the query, e.g. to varbinary(max) or nvarchar(max)"
+    )))
+}
+
+/// The ways of selecting `projection` from `sql` as a derived table, in the
+/// order they are tried: as is, with a trailing `ORDER BY` moved outside, and
+/// with that `ORDER BY` made legal inside by an `OFFSET`.
+fn derived_queries(sql: &str, projection: &str) -> Vec<String> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    let select = |inner: &str, order_by: &str| {
+        format!("SELECT {projection}\nFROM (\n{inner}\n) AS [mssql_result]{order_by}")
+    };
+    let mut queries = vec![select(sql, "")];
+
+    let order_by = keyword_positions(sql, "ORDER")
+        .last()
+        .copied()
+        .filter(|&i| {
+            let by = sql[i + "ORDER".len()..].trim_start();
+            by.get(..2).is_some_and(|by| by.eq_ignore_ascii_case("BY"))
+                && !by[2..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        });
+    if let Some(i) = order_by {
+        let clause = &sql[i..];
+        queries.push(select(sql[..i].trim_end(), &format!("\n{clause}")));
+        if keyword_positions(clause, "OFFSET").is_empty() {
+            queries.push(select(&format!("{sql}\nOFFSET 0 ROWS"), ""));
+        }
+    }
+    queries
+}
+
+/// Decodes a value received for a [`WrappedType`] column.
+pub fn decode_wrapped(
+    ty: WrappedType,
+    value: Value,
+    spatial: SpatialFormat,
+) -> Result<Value, String> {
+    let span = value.span();
+    match (ty, &value) {
+        (_, Value::Nothing { .. }) => Ok(value),
+        (WrappedType::Geometry, Value::Binary { val, .. }) => decode_spatial(val, false, spatial),
+        (WrappedType::Geography, Value::Binary { val, .. }) => decode_spatial(val, true, spatial),
+        (WrappedType::HierarchyId, Value::Binary { val, .. }) => {
+            decode_hierarchyid(val).map(|path| Value::string(path, span))
+        }
+        (WrappedType::Variant, Value::String { val, .. }) => match val.split_once(':') {
+            Some((base_type, text)) => Ok(Value::record(
+                record! {
+                    "base_type" => Value::string(base_type, span),
+                    "value" => variant_value(base_type, text, span),
+                },
+                span,
+            )),
+            None => Err(format!("Unexpected sql_variant value {val}")),
+        },
+        _ => Err(format!("Unexpected value for {ty:?}: {value:?}")),
+    }
+}
+
+/// Converts the text of a `sql_variant` back to a value of its base type.
+fn variant_value(base_type: &str, text: &str, span: Span) -> Value {
+    let parsed = match base_type {
+        "tinyint" | "smallint" | "int" | "bigint" => text.parse().ok().map(|v| Value::int(v, span)),
+        "bit" => Some(Value::bool(text == "1", span)),
+        "real" | "float" => text.parse().ok().map(|v| Value::float(v, span)),
+        // A float cannot hold every exact value, so these keep their text.
+        "decimal" | "numeric" | "money" | "smallmoney" => Some(Value::string(text, span)),
+        "date" => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|naive| utc_date(naive, span)),
+        "datetime" | "datetime2" | "smalldatetime" => {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|naive| utc_date(naive, span))
+        }
+        "datetimeoffset" => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|date| Value::date(date, span)),
+        "binary" | "varbinary" => hex_bytes(text).map(|bytes| Value::binary(bytes, span)),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::string(text, span))
+}
+
+fn utc_date(naive: NaiveDateTime, span: Span) -> Value {
+    let offset = FixedOffset::east_opt(0).expect("zero offset is valid");
+    Value::date(
+        DateTime::<FixedOffset>::from_naive_utc_and_offset(naive, offset),
+        span,
+    )
+}
+
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Reads little-endian values from a serialized CLR type.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or("Unexpected end of spatial data")?;
+        self.offset += N;
+        Ok(bytes.try_into().expect("slice has N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+}
+
+const HAS_Z: u8 = 0x01;
+const HAS_M: u8 = 0x02;
+const SINGLE_POINT: u8 = 0x08;
+const SINGLE_LINE_SEGMENT: u8 = 0x10;
+
+/// A `geometry` or `geography` value read from its CLR serialization.
+#[derive(Debug)]
+struct Spatial {
+    srid: i32,
+    /// Coordinates as x (longitude), y (latitude), z and m, with NaN for a
+    /// missing z or m.
+    points: Vec<[f64; 4]>,
+    /// The index of the first point of each figure.
+    figures: Vec<usize>,
+    /// The parent shape, index of the first figure, and OpenGIS type of each shape.
+    shapes: Vec<(Option<usize>, Option<usize>, u8)>,
+}
+
+impl Spatial {
+    fn read(bytes: &[u8], geography: bool) -> Result<Self, String> {
+        let mut reader = ByteReader { bytes, offset: 0 };
+        let srid = reader.i32()?;
+        let version = reader.u8()?;
+        if version != 1 && version != 2 {
+            return Err(format!(
+                "Unsupported spatial serialization version {version}"
+            ));
+        }
+        let flags = reader.u8()?;
+
+        let count = |reader: &mut ByteReader| -> Result<usize, String> {
+            usize::try_from(reader.i32()?).map_err(|_| "Invalid spatial data".to_string())
+        };
+        let point_count = match flags {
+            _ if flags & SINGLE_POINT != 0 => 1,
+            _ if flags & SINGLE_LINE_SEGMENT != 0 => 2,
+            _ => count(&mut reader)?,
+        };
+
+        let mut points = Vec::with_capacity(point_count);
+        for _ in 0..point_count {
+            let (first, second) = (reader.f64()?, reader.f64()?);
+            // Geography points are serialized latitude first.
+            points.push(match geography {
+                true => [second, first, f64::NAN, f64::NAN],
+                false => [first, second, f64::NAN, f64::NAN],
+            });
+        }
+        for (flag, index) in [(HAS_Z, 2), (HAS_M, 3)] {
+            if flags & flag != 0 {
+                for point in points.iter_mut() {
+                    point[index] = reader.f64()?;
+                }
+            }
+        }
+
+        if flags & (SINGLE_POINT | SINGLE_LINE_SEGMENT) != 0 {
+            let kind = if flags & SINGLE_POINT != 0 { 1 } else { 2 };
+            return Ok(Self {
+                srid,
+                points,
+                figures: vec![0],
+                shapes: vec![(None, Some(0), kind)],
+            });
+        }
+
+        let mut figures = vec![];
+        for _ in 0..count(&mut reader)? {
+            let _attribute = reader.u8()?;
+            figures.push(count(&mut reader)?);
+        }
+
+        let mut shapes = vec![];
+        for _ in 0..count(&mut reader)? {
+            let parent = usize::try_from(reader.i32()?).ok();
+            let figure = usize::try_from(reader.i32()?).ok();
+            let kind = reader.u8()?;
+            shapes.push((parent, figure, kind));
+        }
+
+        Ok(Self {
+            srid,
+            points,
+            figures,
+            shapes,
+        })
+    }
+
+    /// The points of each figure of a shape without children.
+    fn figure_points(&self, shape: usize) -> Vec<&[[f64; 4]]> {
+        let Some(first) = self.shapes[shape].1 else {
+            return vec![];
+        };
+        let end = self.shapes[shape + 1..]
+            .iter()
+            .find_map(|(_, figure, _)| *figure)
+            .unwrap_or(self.figures.len());
+
+        (first..end)
+            .map(|figure| {
+                let start = self.figures[figure];
+                let stop = self
+                    .figures
+                    .get(figure + 1)
+                    .copied()
+                    .unwrap_or(self.points.len());
+                &self.points[start.min(stop)..stop]
+            })
+            .collect()
+    }
+
+    fn children(&self, shape: usize) -> Vec<usize> {
+        (shape + 1..self.shapes.len())
+            .filter(|&child| self.shapes[child].0 == Some(shape))
+            .collect()
+    }
+
+    fn wkt(&self, shape: usize) -> Result<String, String> {
+        let kind = self.shapes[shape].2;
+        let name = match kind {
+            1 => "POINT",
+            2 => "LINESTRING",
+            3 => "POLYGON",
+            4 => "MULTIPOINT",
+            5 => "MULTILINESTRING",
+            6 => "MULTIPOLYGON",
+            7 => "GEOMETRYCOLLECTION",
+            11 => return Ok("FULLGLOBE".to_string()),
+            _ => {
+                return Err(format!(
+                    "Curved spatial shapes (type {kind}) are not supported"
+                ))
+            }
+        };
+
+        let body = match kind {
+            1..=3 => self
+                .figure_points(shape)
+                .iter()
+                .map(|points| wkt_points(points))
+                .collect::<Vec<_>>(),
+            _ => self
+                .children(shape)
+                .into_iter()
+                .map(|child| match kind {
+                    7 => self.wkt(child),
+                    _ => self.wkt(child).map(|text| {
+                        // Members of a multi shape are written without their type name.
+                        text.split_once(' ')
+                            .map(|(_, body)| body.to_string())
+                            .unwrap_or(text)
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        Ok(match (kind, body.is_empty()) {
+            (_, true) => format!("{name} EMPTY"),
+            (1 | 2, false) => format!("{name} {}", body.join(", ")),
+            _ => format!("{name} ({})", body.join(", ")),
+        })
+    }
+
+    fn geojson(&self, shape: usize, span: Span) -> Result<Value, String> {
+        let kind = self.shapes[shape].2;
+        let name = match kind {
+            1 => "Point",
+            2 => "LineString",
+            3 => "Polygon",
+            4 => "MultiPoint",
+            5 => "MultiLineString",
+            6 => "MultiPolygon",
+            7 => "GeometryCollection",
+            11 => "FullGlobe",
+            _ => {
+                return Err(format!(
+                    "Curved spatial shapes (type {kind}) are not supported"
+                ))
+            }
+        };
+
+        let coordinates = |points: &[[f64; 4]]| {
+            Value::list(
+                points
+                    .iter()
+                    .map(|point| geojson_position(point, span))
+                    .collect(),
+                span,
+            )
+        };
+        let figures = self.figure_points(shape);
+        let children = self.children(shape);
+        let (field, value) = match kind {
+            1 => (
+                "coordinates",
+                match figures.first().and_then(|points| points.first()) {
+                    Some(point) => geojson_position(point, span),
+                    None => Value::list(vec![], span),
+                },
+            ),
+            2 => (
+                "coordinates",
+                figures
+                    .first()
+                    .map(|points| coordinates(points))
+                    .unwrap_or(Value::list(vec![], span)),
+            ),
+            3 => (
+                "coordinates",
+                Value::list(
+                    figures.iter().map(|points| coordinates(points)).collect(),
+                    span,
+                ),
+            ),
+            7 => (
+                "geometries",
+                Value::list(
+                    children
+                        .into_iter()
+                        .map(|child| self.geojson(child, span))
+                        .collect::<Result<_, _>>()?,
+                    span,
+                ),
+            ),
+            11 => {
+                return Ok(Value::record(
+                    record! { "type" => Value::string(name, span) },
+                    span,
+                ))
+            }
+            _ => (
+                "coordinates",
+                Value::list(
+                    children
+                        .into_iter()
+                        .map(|child| {
+                            self.geojson(child, span).map(|member| {
+                                member
+                                    .get_data_by_key("coordinates")
+                                    .unwrap_or(Value::nothing(span))
+                            })
+                        })
+                        .collect::<Result<_, _>>()?,
+                    span,
+                ),
+            ),
+        };
+
+        Ok(Value::record(
+            record! {
+                "type" => Value::string(name, span),
+                field => value,
+            },
+            span,
+        ))
+    }
+}
+
+fn wkt_points(points: &[[f64; 4]]) -> String {
+    let point = |point: &[f64; 4]| {
+        let has_m = !point[3].is_nan();
+        let mut values = vec![point[0].to_string(), point[1].to_string()];
+        if !point[2].is_nan() || has_m {
+            values.push(match point[2].is_nan() {
+                true => "NULL".to_string(),
+                false => point[2].to_string(),
+            });
+        }
+        if has_m {
+            values.push(point[3].to_string());
+        }
+        values.join(" ")
+    };
+    format!(
+        "({})",
+        points.iter().map(point).collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn geojson_position(point: &[f64; 4], span: Span) -> Value {
+    let mut position = vec![Value::float(point[0], span), Value::float(point[1], span)];
+    if !point[2].is_nan() {
+        position.push(Value::float(point[2], span));
+    }
+    Value::list(position, span)
+}
+
+/// Decodes the CLR serialization of a `geometry` or `geography` value.
+pub fn decode_spatial(
+    bytes: &[u8],
+    geography: bool,
+    format: SpatialFormat,
+) -> Result<Value, String> {
+    let spatial = Spatial::read(bytes, geography)?;
+    let span = Span::unknown();
+    if spatial.shapes.is_empty() {
+        return Ok(Value::nothing(span));
+    }
+
+    match format {
+        SpatialFormat::Wkt => spatial.wkt(0).map(|text| Value::string(text, span)),
+        SpatialFormat::GeoJson => {
+            let mut value = spatial.geojson(0, span)?;
+            if let Value::Record { val, .. } = &mut value {
+                val.to_mut()
+                    .push("srid", Value::int(spatial.srid as i64, span));
+            }
+            Ok(value)
+        }
+    }
+}
+
+/// The bit patterns of `hierarchyid` labels, with the smallest value each
+/// encodes. The bits before the first `x` select the pattern, `x` bits hold
+/// the value, `0` and `1` are fixed, and `T` is set on the last label of a
+/// level. Labels outside -4168..=5199 use longer patterns, which are not
+/// decoded.
+const HIERARCHYID_PATTERNS: [(i64, &str); 9] = [
+    (0, "01xxT"),
+    (4, "100xxT"),
+    (8, "101xxxT"),
+    (16, "110xx0x1xxxT"),
+    (80, "1110xxx0xxx0x1xxxT"),
+    (1104, "11110xxxxx0xxx0x1xxxT"),
+    (-8, "00111xxxT"),
+    (-72, "0010xx0x1xxxT"),
+    (-4168, "000111xxxxx0xxx0x1xxxT"),
+];
+
+/// Decodes the CLR serialization of a `hierarchyid` into its `/1/2.5/` path.
+pub fn decode_hierarchyid(bytes: &[u8]) -> Result<String, String> {
+    let bit = |index: usize| bytes[index / 8] >> (7 - index % 8) & 1 == 1;
+    let total = bytes.len() * 8;
+
+    let mut position = 0;
+    let mut levels: Vec<String> = vec![];
+    let mut labels: Vec<String> = vec![];
+    'levels: while position < total {
+        for (min, pattern) in HIERARCHYID_PATTERNS {
+            let prefix = pattern.find('x').expect("patterns hold value bits");
+            if position + pattern.len() > total
+                || !pattern[..prefix]
+                    .chars()
+                    .enumerate()
+                    .all(|(i, fixed)| bit(position + i) == (fixed == '1'))
+            {
+                continue;
+            }
+
+            let mut value = 0i64;
+            let mut last = false;
+            for (i, kind) in pattern.chars().enumerate().skip(prefix) {
+                match kind {
+                    'x' => value = value << 1 | bit(position + i) as i64,
+                    'T' => last = bit(position + i),
+                    _ => {}
+                }
+            }
+            position += pattern.len();
+
+            // Labels that are followed by another in the same level are stored one higher.
+            let value = if last { min + value } else { min + value - 1 };
+            labels.push(value.to_string());
+            if last {
+                levels.push(labels.join("."));
+                labels.clear();
+            }
+            continue 'levels;
+        }
+
+        // The last byte is padded with zeros.
+        if (position..total).all(|index| !bit(index)) {
+            break;
+        }
+        return Err("Unsupported hierarchyid label".to_string());
+    }
+
+    if !labels.is_empty() {
+        return Err("Invalid hierarchyid".to_string());
+    }
+    Ok(levels
+        .iter()
+        .map(|level| format!("/{level}"))
+        .collect::<String>()
+        + "/")
+}
+
+#[test]
+fn test_decode_hierarchyid() {
+    assert_eq!(decode_hierarchyid(&[]).unwrap(), "/");
+    assert_eq!(decode_hierarchyid(&[0x58]).unwrap(), "/1/");
+    assert_eq!(decode_hierarchyid(&[0x5B, 0x40]).unwrap(), "/1/2/");
+    assert_eq!(decode_hierarchyid(&[0x62, 0xC0]).unwrap(), "/1.1/");
+    assert_eq!(decode_hierarchyid(&[0x3F, 0x80]).unwrap(), "/-1/");
+}
+
+#[test]
+fn test_decode_spatial() {
+    let mut point = vec![0xE6, 0x10, 0, 0, 1, 0x0C];
+    point.extend(47.5f64.to_le_bytes());
+    point.extend((-122.25f64).to_le_bytes());
+    let value = decode_spatial(&point, true, SpatialFormat::Wkt).unwrap();
+    assert_eq!(value.as_str().unwrap(), "POINT (-122.25 47.5)");
+
+    let mut line = vec![0, 0, 0, 0, 1, 0x04, 2, 0, 0, 0];
+    for coordinate in [0f64, 0.0, 1.0, 1.5] {
+        line.extend(coordinate.to_le_bytes());
+    }
+    line.extend([1, 0, 0, 0, 1, 0, 0, 0, 0]);
+    line.extend([1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 2]);
+    let value = decode_spatial(&line, false, SpatialFormat::Wkt).unwrap();
+    assert_eq!(value.as_str().unwrap(), "LINESTRING (0 0, 1 1.5)");
+
+    let value = decode_spatial(&line, false, SpatialFormat::GeoJson).unwrap();
+    assert_eq!(
+        value.get_data_by_key("type").unwrap().as_str().unwrap(),
+        "LineString"
+    );
+    assert_eq!(
+        value.get_data_by_key("srid"),
+        Some(Value::int(0, Span::unknown()))
+    );
+}
+
+#[test]
+fn test_derived_queries() {
+    let queries = derived_queries(
+        "SELECT * FROM Places ORDER BY Name;",
+        "[mssql_result].[Name]",
+    );
+    assert_eq!(
+        queries,
+        vec![
+            "SELECT [mssql_result].[Name]\nFROM (\nSELECT * FROM Places ORDER BY Name\n) AS [mssql_result]",
+            "SELECT [mssql_result].[Name]\nFROM (\nSELECT * FROM Places\n) AS [mssql_result]\nORDER BY Name",
+            "SELECT [mssql_result].[Name]\nFROM (\nSELECT * FROM Places ORDER BY Name\nOFFSET 0 ROWS\n) AS [mssql_result]",
+        ]
+    );
+
+    let queries = derived_queries(
+        "SELECT ROW_NUMBER() OVER (ORDER BY Name) AS n FROM Places",
+        "n",
+    );
+    assert_eq!(queries.len(), 1);
+    let queries = derived_queries("SELECT * FROM Places ORDER BY Name OFFSET 5 ROWS", "n");
+    assert_eq!(queries.len(), 2);
+}
+
+#[test]
+fn test_variant_value() {
+    let span = Span::test_data();
+    let value = variant_value("decimal", "12345678901234567.89", span);
+    assert_eq!(value.as_str().unwrap(), "12345678901234567.89");
+    assert_eq!(
+        variant_value("money", "1.2345", span).as_str().unwrap(),
+        "1.2345"
+    );
+    assert_eq!(variant_value("int", "42", span).as_int().unwrap(), 42);
+}
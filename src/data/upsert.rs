@@ -0,0 +1,266 @@
+use async_std::{net::TcpStream, stream::StreamExt};
+use nu_protocol::{LabeledError, Value};
+use tiberius::Client;
+
+use super::{bulk_insert, quote_identifier, run_batch, table_columns, BulkOptions, TableColumn};
+
+const STAGING_TABLE: &str = "#mssql_upsert";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpsertSummary {
+    pub inserted: u64,
+    pub updated: u64,
+    pub deleted: u64,
+}
+
+/// Merges `rows` into `table`, matching existing rows on the `keys` columns.
+///
+/// The rows are bulk loaded into a temporary staging table first, then applied
+/// with a single `MERGE` whose `OUTPUT $action` is used to count the changes.
+/// Only the columns present in the first record are staged, so columns left out
+/// of the input keep their current values. Matched rows whose values are
+/// unchanged are not updated. An empty input leaves the table untouched, even
+/// when `delete_missing` is set.
+pub async fn upsert(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    keys: &[String],
+    rows: impl Iterator<Item = Value>,
+    delete_missing: bool,
+) -> Result<UpsertSummary, LabeledError> {
+    let mut rows = rows.peekable();
+    let first = match rows.peek() {
+        Some(Value::Record { val, .. }) => val.clone(),
+        Some(other) => {
+            return Err(
+                LabeledError::new(format!("Expected a table but got {}", other.get_type()))
+                    .with_label("not a record", other.span()),
+            )
+        }
+        None => return Ok(UpsertSummary::default()),
+    };
+
+    let table_columns = table_columns(client, table).await?;
+    let find = |name: &str| {
+        table_columns
+            .iter()
+            .find(|column| column.name.eq_ignore_ascii_case(name))
+    };
+
+    let mut keys_columns: Vec<&TableColumn> = vec![];
+    for key in keys {
+        match find(key) {
+            Some(column) => keys_columns.push(column),
+            None => {
+                return Err(LabeledError::new(format!(
+                    "Key column {key} does not exist"
+                )))
+            }
+        }
+        if !first.iter().any(|(name, _)| name.eq_ignore_ascii_case(key)) {
+            return Err(LabeledError::new(format!(
+                "Key column {key} is missing from the input"
+            )));
+        }
+    }
+
+    let staged: Vec<&TableColumn> = table_columns
+        .iter()
+        .filter(|column| !column.computed && column.sql_type != "timestamp")
+        .filter(|column| {
+            first
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(&column.name))
+        })
+        .collect();
+
+    let staged_list = column_list(&staged, "");
+    // UNION ALL drops the IDENTITY property so identity keys can be staged.
+    let create = format!(
+        "DROP TABLE IF EXISTS {STAGING_TABLE};
+        SELECT TOP 0 {staged_list} INTO {STAGING_TABLE} FROM {table}
+        UNION ALL SELECT TOP 0 {staged_list} FROM {table}"
+    );
+    run_batch(client, &create).await?;
+
+    let result = match bulk_insert(client, STAGING_TABLE, rows, &BulkOptions::default()).await {
+        Ok(_) => merge(client, table, &keys_columns, &staged, delete_missing).await,
+        Err(e) => Err(e),
+    };
+
+    run_batch(client, &format!("DROP TABLE IF EXISTS {STAGING_TABLE}")).await?;
+    result
+}
+
+async fn merge(
+    client: &mut Client<TcpStream>,
+    table: &str,
+    keys: &[&TableColumn],
+    staged: &[&TableColumn],
+    delete_missing: bool,
+) -> Result<UpsertSummary, LabeledError> {
+    let sql = merge_statement(table, keys, staged, delete_missing);
+    let mut stream = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| LabeledError::new(format!("Error merging into {table}: {e}")))?
+        .into_row_stream();
+
+    let mut summary = UpsertSummary::default();
+    while let Some(row) = stream.next().await {
+        let row = row.map_err(|e| LabeledError::new(format!("Error merging into {table}: {e}")))?;
+        let count = row.get::<i64, _>(1).unwrap_or_default() as u64;
+        match row.get::<&str, _>(0) {
+            Some("INSERT") => summary.inserted = count,
+            Some("UPDATE") => summary.updated = count,
+            Some("DELETE") => summary.deleted = count,
+            _ => {}
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The `MERGE` of the staging table into `table`, followed by a count of the
+/// changes per action.
+fn merge_statement(
+    table: &str,
+    keys: &[&TableColumn],
+    staged: &[&TableColumn],
+    delete_missing: bool,
+) -> String {
+    let is_key = |column: &TableColumn| keys.iter().any(|key| key.name == column.name);
+
+    let on = keys
+        .iter()
+        .map(|key| {
+            let name = quote_identifier(&key.name);
+            format!("target.{name} = source.{name}")
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    let updated: Vec<&TableColumn> = staged
+        .iter()
+        .copied()
+        .filter(|column| !is_key(column) && !column.identity)
+        .collect();
+    let inserted: Vec<&TableColumn> = staged
+        .iter()
+        .copied()
+        .filter(|column| !column.identity)
+        .collect();
+
+    let mut sql = format!(
+        "DECLARE @changes TABLE ([action] NVARCHAR(10));
+        MERGE {table} WITH (HOLDLOCK) AS target
+        USING {STAGING_TABLE} AS source ON {on}"
+    );
+
+    if !updated.is_empty() {
+        let set = updated
+            .iter()
+            .map(|column| {
+                let name = quote_identifier(&column.name);
+                format!("target.{name} = source.{name}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        sql.push_str(&format!(
+            "\nWHEN MATCHED AND EXISTS (SELECT {} EXCEPT SELECT {}) THEN UPDATE SET {set}",
+            comparable_list(&updated, "source."),
+            comparable_list(&updated, "target."),
+        ));
+    }
+
+    if !inserted.is_empty() {
+        sql.push_str(&format!(
+            "\nWHEN NOT MATCHED BY TARGET THEN INSERT ({}) VALUES ({})",
+            column_list(&inserted, ""),
+            column_list(&inserted, "source."),
+        ));
+    }
+
+    if delete_missing {
+        sql.push_str("\nWHEN NOT MATCHED BY SOURCE THEN DELETE");
+    }
+
+    sql.push_str(
+        "\nOUTPUT $action INTO @changes;
+        SELECT [action], COUNT_BIG(*) FROM @changes GROUP BY [action];",
+    );
+    sql
+}
+
+fn column_list(columns: &[&TableColumn], prefix: &str) -> String {
+    columns
+        .iter()
+        .map(|column| format!("{prefix}{}", quote_identifier(&column.name)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Like [`column_list`], but with the types `EXCEPT` cannot compare (`xml`,
+/// `text`, `ntext`, `image` and CLR types) cast to ones it can.
+fn comparable_list(columns: &[&TableColumn], prefix: &str) -> String {
+    columns
+        .iter()
+        .map(|column| {
+            let name = format!("{prefix}{}", quote_identifier(&column.name));
+            match column.sql_type.as_str() {
+                "xml" | "ntext" => format!("CAST({name} AS nvarchar(max))"),
+                "text" => format!("CAST({name} AS varchar(max))"),
+                "bigint" | "binary" | "bit" | "char" | "date" | "datetime" | "datetime2"
+                | "datetimeoffset" | "decimal" | "float" | "hierarchyid" | "int" | "money"
+                | "nchar" | "numeric" | "nvarchar" | "real" | "smalldatetime" | "smallint"
+                | "smallmoney" | "sql_variant" | "time" | "timestamp" | "tinyint"
+                | "uniqueidentifier" | "varbinary" | "varchar" => name,
+                _ => format!("CAST({name} AS varbinary(max))"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[test]
+fn test_merge_statement() {
+    let column = |name: &str, sql_type: &str, identity: bool| TableColumn {
+        name: name.to_string(),
+        sql_type: sql_type.to_string(),
+        max_length: 0,
+        precision: 0,
+        scale: 0,
+        nullable: true,
+        identity,
+        computed: false,
+    };
+    let id = column("Id", "int", true);
+    let code = column("Code", "nvarchar", false);
+    let name = column("Name", "nvarchar", false);
+    let notes = column("Notes", "xml", false);
+    let shape = column("Shape", "geometry", false);
+    let staged = [&id, &code, &name, &notes, &shape];
+
+    let sql = merge_statement("[dbo].[Things]", &[&code], &staged, false);
+    assert!(sql.contains(
+        "MERGE [dbo].[Things] WITH (HOLDLOCK) AS target
+        USING #mssql_upsert AS source ON target.[Code] = source.[Code]"
+    ));
+    assert!(sql.contains(
+        "\nWHEN MATCHED AND EXISTS (SELECT source.[Name], CAST(source.[Notes] AS nvarchar(max)), \
+        CAST(source.[Shape] AS varbinary(max)) EXCEPT SELECT target.[Name], \
+        CAST(target.[Notes] AS nvarchar(max)), CAST(target.[Shape] AS varbinary(max))) \
+        THEN UPDATE SET target.[Name] = source.[Name], target.[Notes] = source.[Notes], \
+        target.[Shape] = source.[Shape]"
+    ));
+    assert!(sql.contains(
+        "\nWHEN NOT MATCHED BY TARGET THEN INSERT ([Code], [Name], [Notes], [Shape]) \
+        VALUES (source.[Code], source.[Name], source.[Notes], source.[Shape])"
+    ));
+    assert!(!sql.contains("[Id]"));
+    assert!(!sql.contains("NOT MATCHED BY SOURCE"));
+
+    let sql = merge_statement("[dbo].[Things]", &[&id, &code], &staged, true);
+    assert!(sql.contains("ON target.[Id] = source.[Id] AND target.[Code] = source.[Code]"));
+    assert!(sql.contains("\nWHEN NOT MATCHED BY SOURCE THEN DELETE\nOUTPUT $action INTO @changes;"));
+}
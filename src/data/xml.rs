@@ -0,0 +1,112 @@
+use nu_protocol::{record, Record, Span, Value};
+use roxmltree::{Document, Node, NodeType};
+
+/// Synthetic element wrapping XML fragments, which may have several roots.
+const FRAGMENT_ROOT: &str = "mssql_fragment";
+
+/// Parses XML into the record shape produced by `from xml`.
+///
+/// SQL Server stores XML fragments as well as documents, so the text is parsed
+/// as the content of a wrapping element. A single root element is returned as
+/// its record, anything else as a list of nodes.
+pub fn parse_xml(text: &str) -> Result<Value, String> {
+    let wrapped = format!("<{FRAGMENT_ROOT}>{text}</{FRAGMENT_ROOT}>");
+    let document = Document::parse(&wrapped).map_err(|e| e.to_string())?;
+
+    let mut nodes = xml_nodes(document.root_element());
+    match nodes.as_slice() {
+        [Value::Record { val, .. }] if val.get("tag").is_some_and(|tag| !tag.is_nothing()) => {
+            Ok(nodes.remove(0))
+        }
+        _ => Ok(Value::list(nodes, Span::unknown())),
+    }
+}
+
+/// Converts the children of `node`, skipping whitespace-only text as `from xml` does.
+fn xml_nodes(node: Node) -> Vec<Value> {
+    node.children().filter_map(xml_node).collect()
+}
+
+fn xml_node(node: Node) -> Option<Value> {
+    let span = Span::unknown();
+    let (tag, attributes, content) = match node.node_type() {
+        NodeType::Element => {
+            let attributes: Record = node
+                .attributes()
+                .map(|attribute| {
+                    (
+                        attribute.name().to_string(),
+                        Value::string(attribute.value(), span),
+                    )
+                })
+                .collect();
+            (
+                Value::string(node.tag_name().name(), span),
+                Value::record(attributes, span),
+                Value::list(xml_nodes(node), span),
+            )
+        }
+        NodeType::Text => {
+            let text = node.text().unwrap_or_default();
+            if text.trim().is_empty() {
+                return None;
+            }
+            (
+                Value::nothing(span),
+                Value::nothing(span),
+                Value::string(text, span),
+            )
+        }
+        NodeType::Comment => (
+            Value::string("!", span),
+            Value::nothing(span),
+            Value::string(node.text().unwrap_or_default(), span),
+        ),
+        NodeType::PI => {
+            let pi = node.pi()?;
+            (
+                Value::string(format!("?{}", pi.target), span),
+                Value::nothing(span),
+                Value::string(pi.value.unwrap_or_default(), span),
+            )
+        }
+        NodeType::Root => return None,
+    };
+
+    Some(Value::record(
+        record! {
+            "tag" => tag,
+            "attributes" => attributes,
+            "content" => content,
+        },
+        span,
+    ))
+}
+
+#[test]
+fn test_parse_xml() {
+    let value = parse_xml(r#"<plan id="1"><!--note--><op name="scan">rows</op></plan>"#).unwrap();
+    assert_eq!(
+        value.get_data_by_key("tag").unwrap().as_str().unwrap(),
+        "plan"
+    );
+
+    let content = value.get_data_by_key("content").unwrap();
+    let content = content.as_list().unwrap();
+    assert_eq!(
+        content[0].get_data_by_key("tag").unwrap().as_str().unwrap(),
+        "!"
+    );
+    let text = content[1].get_data_by_key("content").unwrap();
+    assert_eq!(
+        text.as_list().unwrap()[0]
+            .get_data_by_key("content")
+            .unwrap()
+            .as_str()
+            .unwrap(),
+        "rows"
+    );
+
+    let fragment = parse_xml("<a/><b/>").unwrap();
+    assert_eq!(fragment.as_list().unwrap().len(), 2);
+}
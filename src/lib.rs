@@ -2,7 +2,10 @@ mod commands;
 mod data;
 
 use async_std::task;
-use commands::Mssql;
+use commands::{
+    Columns, CreateTable, DataDiff, Databases, Depends, Describe, ErDiagram, Exec, Export, Grep,
+    Import, Insert, Mssql, Proc, SchemaDiff, Script, Tables, ToInserts, Upsert,
+};
 use data::ConnectionPool;
 use nu_plugin::{Plugin, PluginCommand};
 
@@ -40,6 +43,27 @@ impl Plugin for MssqlPlugin {
     }
 
     fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
-        vec![Box::new(Mssql), Box::new(Query)]
+        vec![
+            Box::new(Mssql),
+            Box::new(Query),
+            Box::new(Insert),
+            Box::new(Upsert),
+            Box::new(CreateTable),
+            Box::new(Exec),
+            Box::new(Proc),
+            Box::new(Databases),
+            Box::new(Tables),
+            Box::new(Columns),
+            Box::new(Describe),
+            Box::new(Script),
+            Box::new(SchemaDiff),
+            Box::new(DataDiff),
+            Box::new(ErDiagram),
+            Box::new(Depends),
+            Box::new(Grep),
+            Box::new(Export),
+            Box::new(Import),
+            Box::new(ToInserts),
+        ]
     }
 }
\ No newline at end of file